@@ -0,0 +1,82 @@
+// counts the allocations of the parser with a counting global allocator, run with: cargo bench
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn measure(name: &str, iterations: usize, mut run: impl FnMut()) {
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..iterations {
+        run();
+    }
+    let elapsed = start.elapsed();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+    println!(
+        "{:<40} {:>10.1} allocations/iteration {:>10.2?}/iteration",
+        name,
+        allocations as f64 / iterations as f64,
+        elapsed / iterations as u32
+    );
+}
+
+fn small_documents() {
+    // many small documents, once with a new result per document and once with a reused parser
+    let documents: Vec<String> = (0..100)
+        .map(|index| format!("<order id=\"{index}\"><item>{index}</item><item/></order>"))
+        .collect();
+    measure("parse per document", 100, || {
+        for document in &documents {
+            std::hint::black_box(xml::parse(document).unwrap());
+        }
+    });
+    let mut parser = xml::Parser::new();
+    measure("Parser::parse with recycle", 100, || {
+        for document in &documents {
+            let result = parser.parse(document).unwrap();
+            std::hint::black_box(&result);
+            parser.recycle(result);
+        }
+    });
+}
+
+fn attribute_sparse_document() {
+    // elements without attributes allocate nothing for them, so the sparse document allocates less than the dense one
+    let document = |attribute_every: usize| {
+        let items: String = (0..1000)
+            .map(|index| match index % attribute_every {
+                0 => format!("<item id=\"{index}\">{index}</item>"),
+                _ => format!("<item>{index}</item>"),
+            })
+            .collect();
+        format!("<list>{items}</list>")
+    };
+    let (sparse, dense) = (document(10), document(1));
+    measure("attribute-sparse document", 100, || {
+        std::hint::black_box(xml::parse(&sparse).unwrap());
+    });
+    measure("attribute on every element", 100, || {
+        std::hint::black_box(xml::parse(&dense).unwrap());
+    });
+}
+
+fn main() {
+    small_documents();
+    attribute_sparse_document();
+}
@@ -1,213 +1,4681 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::rc::{Rc, Weak};
 
-#[derive(Debug)]
-pub enum XMLElement<'a> {
-    Element(
-        &'a str,
-        HashMap<&'a str, Vec<&'a str>>,
-        Vec<&'a str>,
-        Vec<XMLElement<'a>>,
-    ),
-    EmptyElement(&'a str, HashMap<&'a str, Vec<&'a str>>),
-    Comment(&'a str),
-    Cdata(&'a str),
+// the attributes are kept in a vector instead of a map: elements without attributes don't allocate,
+// and for the few attributes of a typical tag a linear search is cheaper than hashing
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Attributes<'a> {
+    attributes: Vec<(Cow<'a, str>, Option<Cow<'a, str>>)>, // in the order of the document, attributes without a value like <input disabled> are None
 }
 
-enum XMLParsingSection<'a> {
-    ElementStart(&'a str, HashMap<&'a str, Vec<&'a str>>),
-    ElementStop(&'a str),
-    FinishedElement(XMLElement<'a>),
-    EmptyElement(XMLElement<'a>),
-    Comment(XMLElement<'a>),
-    Cdata(XMLElement<'a>),
-    Content(&'a str),
+impl<'a> Attributes<'a> {
+    pub fn new() -> Self {
+        Attributes {
+            attributes: Vec::new(),
+        }
+    }
+    pub fn get(&self, name: &str) -> Option<&Option<Cow<'a, str>>> {
+        self.attributes
+            .iter()
+            .find(|(attribute_name, _)| attribute_name == name)
+            .map(|(_, value)| value)
+    }
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+    pub fn insert(
+        &mut self,
+        name: Cow<'a, str>,
+        value: Option<Cow<'a, str>>,
+    ) -> Option<Option<Cow<'a, str>>> {
+        // replaces the value of an existing attribute in place, so its position is kept
+        if let Some((_, existing_value)) = self
+            .attributes
+            .iter_mut()
+            .find(|(attribute_name, _)| *attribute_name == name)
+        {
+            return Some(std::mem::replace(existing_value, value));
+        }
+        self.attributes.push((name, value));
+        None
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&Cow<'a, str>, &Option<Cow<'a, str>>)> {
+        self.attributes.iter().map(|(name, value)| (name, value))
+    }
+    pub fn len(&self) -> usize {
+        self.attributes.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.attributes.is_empty()
+    }
 }
 
-fn parse_element_name_and_attributes(raw_xml: &str) -> (&str, HashMap<&str, Vec<&str>>) {
-    if let Some((name, raw_attributes)) = raw_xml.split_once(' ')
-    // removes the pre- and suffix as well as split the tag into the name and the attribute list: <name attribute_one="one two" attribute_two="one two"> -> name & attribute_one="one two" attribute_two="one two"
-    {
-        let mut attributes: HashMap<&str, Vec<&str>> = HashMap::<&str, Vec<&str>>::new();
-        for attribute_pair in raw_attributes.split("\" ").collect::<Vec<&str>>() {
-            // splits the attribute list into name and value pairs: attribute_one="one two" attribute_two="one two" -> attribute_one="one two & attribute_two="one two"
-            let (name, mut values) = attribute_pair.split_once("=\"").unwrap();
-            if let Some(stripped_values) = values.strip_suffix('\"') {
-                // the last one will have one final quotation mark
-                values = stripped_values;
-            }
-            attributes.insert(
-                name,
-                values.split(" ").collect::<Vec<&str>>(), // converts the value list into a vector: one two -> [one, two]
-            );
-        }
-        return (name, attributes);
+impl fmt::Debug for Attributes<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
     }
-    (
-        raw_xml, // if the stripped_xml does not contain a whitespace, it is the name of the element and there are no attributes
-        HashMap::<&str, Vec<&str>>::new(),
-    )
-}
-fn parse_version(raw_xml: &str) -> XMLParsingSection {
-    let stripped_xml = raw_xml
-        .strip_prefix("<?")
-        .unwrap()
-        .strip_suffix("?>")
-        .unwrap();
-    let (name, attributes) = parse_element_name_and_attributes(stripped_xml);
-    XMLParsingSection::EmptyElement(XMLElement::EmptyElement(name, attributes))
-}
-fn parse_element_start_tag(raw_xml: &str) -> XMLParsingSection {
-    let stripped_xml = raw_xml
-        .strip_prefix("<")
-        .unwrap()
-        .strip_suffix(">")
-        .unwrap();
-    let (name, attributes) = parse_element_name_and_attributes(stripped_xml);
-    XMLParsingSection::ElementStart(name, attributes)
 }
-fn parse_element_stop_tag(raw_xml: &str) -> XMLParsingSection {
-    XMLParsingSection::ElementStop(
-        raw_xml
-            .strip_prefix("</")
-            .unwrap()
-            .strip_suffix(">")
-            .unwrap(),
-    )
-    // remove the pre- and suffix of the end-tag: </name> -> name
-}
-fn parse_empty_element_tag(raw_xml: &str) -> XMLParsingSection {
-    let stripped_xml = raw_xml
-        .strip_prefix("<")
-        .unwrap()
-        .strip_suffix("/>")
-        .unwrap();
-    let (name, attributes) = parse_element_name_and_attributes(stripped_xml);
-    XMLParsingSection::EmptyElement(XMLElement::EmptyElement(name, attributes))
+
+impl<'a> FromIterator<(Cow<'a, str>, Option<Cow<'a, str>>)> for Attributes<'a> {
+    fn from_iter<T: IntoIterator<Item = (Cow<'a, str>, Option<Cow<'a, str>>)>>(iter: T) -> Self {
+        let mut attributes = Attributes::new();
+        for (name, value) in iter {
+            attributes.insert(name, value);
+        }
+        attributes
+    }
 }
-fn parse_comment(raw_xml: &str) -> XMLParsingSection {
-    XMLParsingSection::Comment(XMLElement::Comment(
-        raw_xml
-            .strip_prefix("<!-- ")
-            .unwrap()
-            .strip_suffix(" -->")
-            .unwrap(),
-    ))
-    // remove the pre- and suffix of the end-tag: <!-- comment --> ->  comment
+
+#[derive(Debug)]
+pub enum XMLElement<'a> {
+    Element(Cow<'a, str>, Attributes<'a>, Vec<XMLElement<'a>>), // the children are in document order, including the text in between them
+    EmptyElement(Cow<'a, str>, Attributes<'a>),
+    Comment(Cow<'a, str>),
+    Cdata(Cow<'a, str>),
+    Content(Cow<'a, str>), // text as it is written in the document, its entities are not decoded: a &amp; b
+    Text(Cow<'a, str>), // text with its entities decoded, by ParseOptions::decode_entities or ElementBuilder::text: a & b
+    Doctype(Cow<'a, str>),
+    ProcessingInstruction(Cow<'a, str>, Cow<'a, str>), // the target and the data: <?target data?>
 }
-fn parse_cdata(raw_xml: &str) -> XMLParsingSection {
-    XMLParsingSection::Cdata(XMLElement::Cdata(
-        raw_xml
-            .strip_prefix("<![CDATA[")
-            .unwrap()
-            .strip_suffix("]]>")
-            .unwrap(),
-    ))
-    // remove the pre- and suffix of the end-tag: <![CDATA[cdata]]> ->  cdata
+
+pub type OwnedXMLElement = XMLElement<'static>; // does not borrow from the parsed document
+
+impl Drop for XMLElement<'_> {
+    fn drop(&mut self) {
+        // moves the descendants onto a stack before they are dropped, so a deep tree can't overflow the call stack
+        let XMLElement::Element(_, _, children) = self else {
+            return;
+        };
+        let mut stack = std::mem::take(children);
+        while let Some(mut node) = stack.pop() {
+            if let XMLElement::Element(_, _, children) = &mut node {
+                stack.append(children); // the node is dropped without children
+            }
+        }
+    }
 }
 
-pub fn parse(raw_xml: &str) -> Vec<XMLElement> {
-    let mut result = Vec::<XMLElement>::new();
-    let mut section_stack = Vec::<XMLParsingSection>::new();
-    for mut section in raw_xml.split_inclusive('>').collect::<Vec<&str>>() {
-        if !section.starts_with('<') {
-            if let Some(index) = section.find('<') {
-                let (content, update_section) = section.split_at(index);
-                section = update_section;
-                if !content.chars().all(|x| x == '\n' || x == ' ') {
-                    // if the section is only newlines or spaces, it can be omitted
-                    section_stack.push(XMLParsingSection::Content(content));
-                }
-            } else if !section.chars().all(|x| x == '\n' || x == ' ') {
-                // if the section is only newlines or spaces, it can be omitted
-                section_stack.push(XMLParsingSection::Content(section));
+impl<'a> XMLElement<'a> {
+    pub fn name(&self) -> Option<&str> {
+        match self {
+            XMLElement::Element(name, _, _) | XMLElement::EmptyElement(name, _) => Some(name),
+            _ => None, // comments, CDATA and contents have no name
+        }
+    }
+    pub fn attributes(&self) -> Option<&Attributes<'a>> {
+        match self {
+            XMLElement::Element(_, attributes, _) | XMLElement::EmptyElement(_, attributes) => {
+                Some(attributes)
             }
+            _ => None,
         }
-        if section.ends_with("/>") {
-            // empty-element tag
-            if section_stack.is_empty() {
-                // there is currently no parent element
-                if let XMLParsingSection::EmptyElement(element) = parse_empty_element_tag(section) {
-                    result.push(element);
+    }
+    pub fn attributes_mut(&mut self) -> Option<&mut Attributes<'a>> {
+        match self {
+            XMLElement::Element(_, attributes, _) | XMLElement::EmptyElement(_, attributes) => {
+                Some(attributes)
+            }
+            _ => None,
+        }
+    }
+    pub fn children(&self) -> &[XMLElement<'a>] {
+        match self {
+            XMLElement::Element(_, _, children) => children,
+            _ => &[], // only elements with a start- and end-tag can have children
+        }
+    }
+    pub fn into_parts(mut self) -> Option<(Cow<'a, str>, Attributes<'a>, Vec<XMLElement<'a>>)> {
+        // moves the name, attributes and children out of an element, which can't be destructured by value as XMLElement implements Drop
+        // an empty element has no children, the other nodes have no parts and return None
+        match &mut self {
+            XMLElement::Element(name, attributes, children) => Some((
+                std::mem::take(name),
+                std::mem::take(attributes),
+                std::mem::take(children),
+            )),
+            XMLElement::EmptyElement(name, attributes) => {
+                Some((std::mem::take(name), std::mem::take(attributes), Vec::new()))
+            }
+            _ => None,
+        }
+    }
+    pub fn into_text(mut self) -> Option<Cow<'a, str>> {
+        // like into_parts, moves the only string out of a comment, CDATA, content, text or doctype, the other nodes return None
+        match &mut self {
+            XMLElement::Comment(text)
+            | XMLElement::Cdata(text)
+            | XMLElement::Content(text)
+            | XMLElement::Text(text)
+            | XMLElement::Doctype(text) => Some(std::mem::take(text)),
+            _ => None,
+        }
+    }
+    pub fn children_named<'b>(
+        &'b self,
+        name: &'b str,
+    ) -> impl Iterator<Item = &'b XMLElement<'a>> + 'b {
+        // only the direct children with the name, unlike find_all which also searches their descendants
+        self.children()
+            .iter()
+            .filter(move |child| child.name() == Some(name))
+    }
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            XMLElement::Element(_, _, children) => children.iter().find_map(|child| match child {
+                XMLElement::Content(content) | XMLElement::Text(content) => Some(&**content), // the first content of the element
+                _ => None,
+            }),
+            XMLElement::EmptyElement(_, _) => None,
+            XMLElement::Comment(text)
+            | XMLElement::Cdata(text)
+            | XMLElement::Content(text)
+            | XMLElement::Text(text) => Some(text),
+            XMLElement::ProcessingInstruction(_, data) => Some(data),
+            XMLElement::Doctype(_) => None,
+        }
+    }
+    pub fn text_raw(&self) -> Option<&str> {
+        // the untrimmed text, the same as text() including any surrounding whitespace: <t>  hi  </t> -> "  hi  "
+        // a content is the slice of the document as it is written, but a text decoded by ParseOptions::decode_entities stays decoded
+        self.text()
+    }
+    pub fn text_trimmed(&self) -> Option<&str> {
+        // the text without its leading and trailing whitespace: <t>  hi  </t> -> hi
+        self.text().map(|text| text.trim_matches(is_whitespace))
+    }
+    pub fn get_attr(&self, name: &str) -> Option<&str> {
+        // the raw value of an attribute, as written between the quotation marks: href="a b" -> a b
+        // an attribute without a value like <input disabled> has an empty value
+        Some(self.attributes()?.get(name)?.as_deref().unwrap_or_default())
+    }
+    pub fn get_attr_decoded(&self, name: &str) -> Option<Cow<'_, str>> {
+        // the value of an attribute with its entities decoded: title="Tom &amp; Jerry" -> Tom & Jerry
+        self.get_attr(name).map(decode_entities)
+    }
+    pub fn attribute_tokens(&self, name: &str) -> Vec<&str> {
+        // splits a space-separated attribute value into its tokens: class="one two" -> [one, two]
+        match self {
+            XMLElement::Element(_, attributes, _) | XMLElement::EmptyElement(_, attributes) => {
+                attributes
+                    .get(name)
+                    .and_then(Option::as_deref)
+                    .map(|value| value.split_whitespace().collect())
+                    .unwrap_or_default()
+            }
+            _ => Vec::new(),
+        }
+    }
+    pub fn find_all(&self, name: &str) -> Vec<&XMLElement<'a>> {
+        // collects this element and all of its descendants with the given name, in document order
+        let mut result = Vec::new();
+        self.collect_matching(
+            &|element: &XMLElement| element.name() == Some(name),
+            &mut result,
+        );
+        result
+    }
+    pub fn child_text_map(&self) -> HashMap<&str, String> {
+        // maps the name of every child element to its inner text, a repeated name keeps the text of its last element: <config><port>8080</port></config> -> port: 8080
+        self.children()
+            .iter()
+            .filter_map(|child| Some((child.name()?, child.inner_text())))
+            .collect()
+    }
+    pub fn find_by_attr(&self, name: &str, value: &str) -> Vec<&XMLElement<'a>> {
+        // collects this element and all of its descendants with the given attribute value, compared without decoding entities: id="main"
+        let mut result = Vec::new();
+        self.collect_matching(
+            &|element: &XMLElement| element.get_attr(name) == Some(value),
+            &mut result,
+        );
+        result
+    }
+    pub fn find_first(&self, name: &str) -> Option<&XMLElement<'a>> {
+        std::iter::once(self)
+            .chain(self.descendants())
+            .find(|element| element.name() == Some(name))
+    }
+    pub fn select(&self, path: &str) -> Vec<&XMLElement<'a>> {
+        // resolves a simple path against this element, which is the context of the first step:
+        // - name/name selects the direct children with the second name of an element with the first name: catalog/book/title
+        // - name//name selects all descendants with the second name: catalog//title
+        // - a path starting with // selects the matching elements anywhere in the tree, including this element: //title
+        // - * matches elements with any name: catalog/*/title
+        // attributes, predicates and any other part of XPath are not supported
+        let mut selected = Vec::<&XMLElement<'a>>::new();
+        let mut descendant = path.starts_with("//");
+        let mut first_step = true;
+        for step in path.trim_start_matches('/').split('/') {
+            if step.is_empty() {
+                descendant = true; // the empty step between two slashes: name//name
+                continue;
+            }
+            let matches = |element: &XMLElement| {
+                element.name().is_some() && (step == "*" || element.name() == Some(step))
+            };
+            let mut candidates = Vec::<&XMLElement<'a>>::new();
+            if first_step {
+                if descendant {
+                    self.collect_matching(&matches, &mut candidates);
+                } else if matches(self) {
+                    candidates.push(self);
                 }
             } else {
-                section_stack.push(parse_empty_element_tag(section));
+                for context in selected {
+                    for child in context.children() {
+                        if descendant {
+                            child.collect_matching(&matches, &mut candidates);
+                        } else if matches(child) {
+                            candidates.push(child);
+                        }
+                    }
+                }
             }
-        } else if section.starts_with("</") {
-            // end-tag
-            if let XMLParsingSection::ElementStop(parent_name) = parse_element_stop_tag(section) {
-                let mut contents = Vec::<&str>::new();
-                let mut children = Vec::<XMLElement>::new();
-                loop {
-                    if let Some(section) = section_stack.pop() {
-                        match section {
-                            XMLParsingSection::ElementStart(name, attributes) => {
-                                if name == parent_name {
-                                    // the start tag of the stop tag was found -> end the parsing of this element
-                                    children.reverse(); // as they are added in reverse order, they have to be inversed again
-                                    section_stack.push(XMLParsingSection::FinishedElement(
-                                        XMLElement::Element(name, attributes, contents, children),
-                                    ));
-                                    break;
-                                }
-                            }
-                            XMLParsingSection::ElementStop(_) => {
-                                // this should never happen
-                            }
-                            XMLParsingSection::FinishedElement(element) => {
-                                children.push(element);
-                            }
-                            XMLParsingSection::EmptyElement(element) => {
-                                children.push(element);
-                            }
-                            XMLParsingSection::Comment(element) => {
-                                children.push(element);
-                            }
-                            XMLParsingSection::Cdata(element) => {
-                                children.push(element);
+            if descendant && !first_step {
+                // nested matches of name//name are found once for every matching ancestor, any other step finds every element once
+                let mut seen = HashSet::new();
+                candidates.retain(|candidate| seen.insert(std::ptr::from_ref(*candidate)));
+            }
+            selected = candidates;
+            descendant = false;
+            first_step = false;
+        }
+        selected
+    }
+    fn collect_matching<'b>(
+        &'b self,
+        matches: &impl Fn(&XMLElement) -> bool,
+        result: &mut Vec<&'b XMLElement<'a>>,
+    ) {
+        // collects this element and all of its matching descendants, in document order
+        result.extend(
+            std::iter::once(self)
+                .chain(self.descendants())
+                .filter(|element| matches(element)),
+        );
+    }
+    pub fn count_matching(&self, predicate: impl Fn(&XMLElement) -> bool) -> usize {
+        // counts this element and all of its descendants that match, without collecting them
+        std::iter::once(self)
+            .chain(self.descendants())
+            .filter(|node| predicate(node))
+            .count()
+    }
+    pub fn find_all_mut(&mut self, name: &str) -> Vec<&mut XMLElement<'a>> {
+        // like find_all, but an element inside of another match is not returned on its own, as it can be changed through the outer match
+        // use for_each_mut to change the nested matches as well
+        let mut result = Vec::new();
+        let mut stack = vec![self]; // like for_each_mut, the elements are visited from a stack in document order
+        while let Some(element) = stack.pop() {
+            if element.name() == Some(name) {
+                result.push(element);
+            } else if let XMLElement::Element(_, _, children) = element {
+                stack.extend(children.iter_mut().rev());
+            }
+        }
+        result
+    }
+    pub fn for_each_mut(&mut self, name: &str, mut f: impl FnMut(&mut XMLElement<'a>)) {
+        // calls f for every element with the given name in document order, also for those inside of another match
+        // f is called for an element before its children, so the children it adds or changes are visited too
+        let mut stack = vec![self];
+        while let Some(element) = stack.pop() {
+            if element.name() == Some(name) {
+                f(element);
+            }
+            if let XMLElement::Element(_, _, children) = element {
+                stack.extend(children.iter_mut().rev());
+            }
+        }
+    }
+    pub fn strip_comments(&mut self) {
+        // removes every comment below this element, the other nodes keep their order
+        let mut stack = vec![self]; // the elements are visited from a stack, so a deep tree can't overflow the call stack
+        while let Some(element) = stack.pop() {
+            if let XMLElement::Element(_, _, children) = element {
+                children.retain(|child| !matches!(child, XMLElement::Comment(_)));
+                stack.extend(children.iter_mut());
+            }
+        }
+    }
+    pub fn insert_child(
+        &mut self,
+        index: usize,
+        child: XMLElement<'a>,
+    ) -> Result<(), XMLElement<'a>> {
+        // returns the child back if the index is after the last child or this node can't have children
+        if let XMLElement::EmptyElement(name, attributes) = self {
+            if index == 0 {
+                // an empty element gets a start- and end-tag: <a/> -> <a><b/></a>
+                *self = XMLElement::Element(
+                    std::mem::take(name),
+                    std::mem::take(attributes),
+                    Vec::new(),
+                );
+            }
+        }
+        match self {
+            XMLElement::Element(_, _, children) if index <= children.len() => {
+                children.insert(index, child);
+                Ok(())
+            }
+            _ => Err(child),
+        }
+    }
+    pub fn push_child(&mut self, child: XMLElement<'a>) -> Result<(), XMLElement<'a>> {
+        self.insert_child(self.children().len(), child)
+    }
+    pub fn remove_child(&mut self, index: usize) -> Option<XMLElement<'a>> {
+        // the element keeps its start- and end-tag after its last child was removed: <a><b/></a> -> <a></a>
+        match self {
+            XMLElement::Element(_, _, children) if index < children.len() => {
+                Some(children.remove(index))
+            }
+            _ => None,
+        }
+    }
+    pub fn rename_all(&mut self, from: &str, to: &str) {
+        // renames this element and every element below it with the given name: <old><old/></old> -> <new><new/></new>
+        let mut stack = vec![self];
+        while let Some(element) = stack.pop() {
+            if let XMLElement::Element(name, _, _) | XMLElement::EmptyElement(name, _) = element {
+                if name == from {
+                    *name = Cow::Owned(to.to_string());
+                }
+            }
+            if let XMLElement::Element(_, _, children) = element {
+                stack.extend(children.iter_mut());
+            }
+        }
+    }
+    pub fn rename_attr_all(&mut self, from: &str, to: &str) {
+        // renames the attribute in this element and every element below it, keeping its value and position
+        // an element that already has an attribute with the new name is left unchanged, so no name is duplicated: <a x="1" y="2"/>
+        let mut stack = vec![self];
+        while let Some(element) = stack.pop() {
+            if let Some(attributes) = element
+                .attributes_mut()
+                .filter(|attributes| !attributes.contains_key(to))
+            {
+                for (name, _) in &mut attributes.attributes {
+                    if name == from {
+                        *name = Cow::Owned(to.to_string());
+                    }
+                }
+            }
+            if let XMLElement::Element(_, _, children) = element {
+                stack.extend(children.iter_mut());
+            }
+        }
+    }
+    pub fn without_comments(mut self) -> Self {
+        self.strip_comments();
+        self
+    }
+    pub fn accept(&self, visitor: &mut impl Visitor) {
+        // calls the visitor for this node and then for every node below it in document order
+        for node in std::iter::once(self).chain(self.descendants()) {
+            match node {
+                XMLElement::Element(_, _, _) | XMLElement::EmptyElement(_, _) => {
+                    visitor.visit_element(node)
+                }
+                XMLElement::Content(text) | XMLElement::Text(text) => visitor.visit_text(text),
+                XMLElement::Comment(text) => visitor.visit_comment(text),
+                XMLElement::Cdata(text) => visitor.visit_cdata(text),
+                XMLElement::Doctype(_) | XMLElement::ProcessingInstruction(_, _) => {}
+            }
+        }
+    }
+    pub fn descendants(&self) -> Descendants<'_, 'a> {
+        Descendants {
+            stack: vec![self.children().iter()],
+        }
+    }
+    pub fn inner_text(&self) -> String {
+        // concatenates the text of all contents and CDATA in this element: <a>one <b>two</b> three</a> -> one two three
+        // the entities of the contents are decoded, texts that were already decoded while parsing are taken as they are
+        let mut inner_text = String::new();
+        for node in std::iter::once(self).chain(self.descendants()) {
+            match node {
+                XMLElement::Content(content) => inner_text.push_str(&decode_entities(content)),
+                XMLElement::Text(text) => inner_text.push_str(text),
+                XMLElement::Cdata(cdata) => inner_text.push_str(cdata), // CDATA is never decoded
+                _ => {}
+            }
+        }
+        inner_text
+    }
+    pub fn flatten(&self) -> Vec<(String, String)> {
+        // pairs every text of the tree with the path of its element, repeated siblings are numbered from 1: <a><b>x</b><b>y</b></a> -> a/b[1] & x, a/b[2] & y
+        let mut result = Vec::new();
+        let Some(name) = self.name() else {
+            return result;
+        };
+        // the nodes that are still to be visited with the path of their element, kept on a stack in document order instead of recursion
+        let mut stack = vec![(self, name.to_string())];
+        while let Some((node, path)) = stack.pop() {
+            match node {
+                XMLElement::Content(content) => {
+                    result.push((path, decode_entities(content).into_owned()));
+                }
+                XMLElement::Text(text) | XMLElement::Cdata(text) => {
+                    result.push((path, text.to_string()));
+                }
+                XMLElement::Element(_, _, children) => {
+                    let mut counts = HashMap::<&str, usize>::new(); // how often each name appears among the children
+                    for name in children.iter().filter_map(XMLElement::name) {
+                        *counts.entry(name).or_default() += 1;
+                    }
+                    let mut positions = HashMap::<&str, usize>::new();
+                    let mut visited = Vec::with_capacity(children.len());
+                    for child in children {
+                        match child {
+                            XMLElement::Element(name, _, _) => {
+                                let child_path = if counts[&**name] > 1 {
+                                    let position = positions.entry(name).or_default();
+                                    *position += 1;
+                                    format!("{}/{}[{}]", path, name, position)
+                                } else {
+                                    format!("{}/{}", path, name)
+                                };
+                                visited.push((child, child_path));
                             }
-                            XMLParsingSection::Content(content) => {
-                                contents.push(content);
+                            XMLElement::EmptyElement(name, _) => {
+                                *positions.entry(name).or_default() += 1; // has no text, but still counts as a sibling
                             }
+                            _ => visited.push((child, path.clone())), // a text belongs to the path of its parent
                         }
                     }
+                    stack.extend(visited.into_iter().rev());
                 }
+                _ => {}
             }
-        } else if section.starts_with("<?") {
-            // start-tag
-            if let XMLParsingSection::EmptyElement(element) = parse_version(section) {
-                result.push(element)
+        }
+        result
+    }
+    pub fn prefix(&self) -> Option<&str> {
+        // the namespace prefix of the name: ns:name -> ns
+        self.name()?.split_once(':').map(|(prefix, _)| prefix)
+    }
+    pub fn local_name(&self) -> Option<&str> {
+        // the name without its namespace prefix: ns:name -> name
+        let name = self.name()?;
+        Some(
+            name.split_once(':')
+                .map_or(name, |(_, local_name)| local_name),
+        )
+    }
+    pub fn resolve_prefix<'b>(
+        &'b self,
+        prefix: &str,
+        ancestors: &[&'b XMLElement<'a>],
+    ) -> Option<&'b str> {
+        // finds the namespace declared for the prefix by this element or the closest of its ancestors, which are ordered from the root to the parent
+        if prefix == "xml" {
+            return Some("http://www.w3.org/XML/1998/namespace"); // the xml prefix is bound by definition
+        }
+        let declaration = format!("xmlns:{}", prefix);
+        std::iter::once(self)
+            .chain(ancestors.iter().rev().copied())
+            .find_map(|element| element.attributes()?.get(&declaration))
+            .and_then(|uri| uri.as_deref())
+    }
+    pub fn namespace_uri<'b>(&'b self, ancestors: &[&'b XMLElement<'a>]) -> Option<&'b str> {
+        // the namespace of the element, unprefixed elements are in the closest default namespace declared with xmlns="..."
+        if let Some(prefix) = self.prefix() {
+            return self.resolve_prefix(prefix, ancestors);
+        }
+        std::iter::once(self)
+            .chain(ancestors.iter().rev().copied())
+            .find_map(|element| element.attributes()?.get("xmlns"))
+            .and_then(|uri| uri.as_deref())
+            .filter(|uri| !uri.is_empty()) // xmlns="" removes the default namespace
+    }
+    pub fn get_attr_ns<'b>(
+        &'b self,
+        uri: &str,
+        local_name: &str,
+        ancestors: &[&'b XMLElement<'a>],
+    ) -> Option<&'b str> {
+        // the raw value of an attribute by the namespace its prefix is bound to, so xlink:href and l:href are found alike
+        // unprefixed attributes are in no namespace, even if a default namespace is declared
+        self.attributes()?.iter().find_map(|(name, value)| {
+            let (prefix, attribute_local_name) = name.split_once(':')?;
+            if prefix == "xmlns" || attribute_local_name != local_name {
+                return None; // namespace declarations are not attributes of a namespace
             }
-        } else if section.starts_with('<') {
-            // start-tag
-            section_stack.push(parse_element_start_tag(section)); // always push to stack to make it the current parent element
-        } else if section.starts_with("<!--") {
-            // comment
-            if section_stack.is_empty() {
-                // there is currently no parent element
-                if let XMLParsingSection::Comment(element) = parse_comment(section) {
-                    result.push(element);
+            (self.resolve_prefix(prefix, ancestors)? == uri)
+                .then(|| value.as_deref().unwrap_or_default())
+        })
+    }
+    pub fn ancestors_of<'b>(&'b self, descendant: &XMLElement) -> Option<Vec<&'b XMLElement<'a>>> {
+        // the path from this element to the parent of the descendant, as needed to resolve its namespace
+        // the path is followed with a stack of the unvisited children of each element on it, like Descendants
+        let mut ancestors = vec![self];
+        let mut stack = vec![self.children().iter()];
+        while let Some(children) = stack.last_mut() {
+            let Some(child) = children.next() else {
+                stack.pop();
+                ancestors.pop();
+                continue;
+            };
+            if std::ptr::eq(child, descendant) {
+                return Some(ancestors);
+            }
+            if !child.children().is_empty() {
+                ancestors.push(child);
+                stack.push(child.children().iter());
+            }
+        }
+        None
+    }
+    pub fn structurally_eq(&self, other: &XMLElement) -> bool {
+        // compares two trees, ignoring the order of attributes and whitespace-only contents, so <a x="1" y="2"> <b/> </a> equals <a y="2" x="1"><b/></a>
+        // an element without children equals an empty element: <a></a> equals <a/>
+        let is_significant = |node: &&XMLElement| match node {
+            XMLElement::Content(text) | XMLElement::Text(text) => !text.chars().all(is_whitespace),
+            _ => true,
+        };
+        // the pairs of nodes that are still to be compared, kept on a stack instead of recursion
+        let mut pairs = vec![(self, other)];
+        while let Some((node, other_node)) = pairs.pop() {
+            let equal = match (node, other_node) {
+                (
+                    XMLElement::Element(..) | XMLElement::EmptyElement(..),
+                    XMLElement::Element(..) | XMLElement::EmptyElement(..),
+                ) => {
+                    let (attributes, other_attributes) =
+                        (node.attributes(), other_node.attributes());
+                    let children = node.children().iter().filter(is_significant);
+                    let other_children = other_node.children().iter().filter(is_significant);
+                    let equal = node.name() == other_node.name()
+                        && attributes.map(Attributes::len) == other_attributes.map(Attributes::len)
+                        && attributes.into_iter().flat_map(Attributes::iter).all(
+                            |(name, value)| {
+                                other_attributes.and_then(|attributes| attributes.get(name))
+                                    == Some(value)
+                            },
+                        )
+                        && children.clone().count() == other_children.clone().count();
+                    pairs.extend(children.zip(other_children));
+                    equal
                 }
-            } else {
-                section_stack.push(parse_comment(section));
+                (XMLElement::Content(content), XMLElement::Content(other_content)) => {
+                    decode_entities(content) == decode_entities(other_content) // like a content and a text, so &amp; equals &#38;
+                }
+                (XMLElement::Comment(text), XMLElement::Comment(other_text))
+                | (XMLElement::Cdata(text), XMLElement::Cdata(other_text))
+                | (XMLElement::Text(text), XMLElement::Text(other_text))
+                | (XMLElement::Doctype(text), XMLElement::Doctype(other_text)) => {
+                    text == other_text
+                }
+                (XMLElement::Content(content), XMLElement::Text(text))
+                | (XMLElement::Text(text), XMLElement::Content(content)) => {
+                    decode_entities(content) == *text // the same text, once decoded while parsing and once not
+                }
+                (
+                    XMLElement::ProcessingInstruction(target, data),
+                    XMLElement::ProcessingInstruction(other_target, other_data),
+                ) => target == other_target && data == other_data,
+                _ => false,
+            };
+            if !equal {
+                return false;
             }
-        } else if section.starts_with("<![CDATA[") {
-            // CDATA
-            if section_stack.is_empty() {
-                // there is currently no parent element
-                if let XMLParsingSection::Cdata(element) = parse_cdata(section) {
-                    result.push(element);
+        }
+        true
+    }
+    pub fn to_owned(&self) -> OwnedXMLElement {
+        // copies every borrowed string, so the result outlives the parsed document
+        // the copies of the elements whose children are not all copied yet are kept on a stack instead of recursion
+        let mut stack = Vec::<(OwnedXMLElement, std::slice::Iter<XMLElement<'a>>)>::new();
+        let mut copied = Some(self.to_owned_node());
+        if let XMLElement::Element(_, _, children) = self {
+            stack.push((copied.take().unwrap(), children.iter()));
+        }
+        while let Some((element, children)) = stack.last_mut() {
+            if let (Some(child), XMLElement::Element(_, _, copied_children)) =
+                (copied.take(), &mut *element)
+            {
+                copied_children.push(child);
+            }
+            match children.next() {
+                Some(child @ XMLElement::Element(_, _, grandchildren)) => {
+                    stack.push((child.to_owned_node(), grandchildren.iter()));
                 }
-            } else {
-                section_stack.push(parse_cdata(section));
+                Some(child) => copied = Some(child.to_owned_node()),
+                None => copied = stack.pop().map(|(element, _)| element),
             }
         }
+        copied.unwrap()
     }
-    for element in section_stack {
-        // adding any remaining elements to the result
-        match element {
-            XMLParsingSection::FinishedElement(element) => result.push(element),
-            _ => {}
+    fn to_owned_node(&self) -> OwnedXMLElement {
+        // copies the node without its children
+        match self {
+            XMLElement::Element(name, attributes, children) => XMLElement::Element(
+                to_owned_text(name),
+                to_owned_attributes(attributes),
+                Vec::with_capacity(children.len()),
+            ),
+            XMLElement::EmptyElement(name, attributes) => {
+                XMLElement::EmptyElement(to_owned_text(name), to_owned_attributes(attributes))
+            }
+            XMLElement::Comment(comment) => XMLElement::Comment(to_owned_text(comment)),
+            XMLElement::Cdata(cdata) => XMLElement::Cdata(to_owned_text(cdata)),
+            XMLElement::Content(content) => XMLElement::Content(to_owned_text(content)),
+            XMLElement::Text(text) => XMLElement::Text(to_owned_text(text)),
+            XMLElement::Doctype(doctype) => XMLElement::Doctype(to_owned_text(doctype)),
+            XMLElement::ProcessingInstruction(target, data) => {
+                XMLElement::ProcessingInstruction(to_owned_text(target), to_owned_text(data))
+            }
+        }
+    }
+    pub fn to_pretty_string(&self, indent: &str) -> String {
+        // writes every child on its own line, indented once more than its parent:
+        // <a><b/></a> -> <a>
+        //    <b/>
+        // </a>
+        let mut pretty_string = String::new();
+        self.write_pretty(&mut pretty_string, indent);
+        pretty_string
+    }
+    fn write_pretty(&self, pretty_string: &mut String, indent: &str) {
+        use fmt::Write;
+        // like Display, the nodes and end-tags that are still to be written are kept on a stack, each with its level
+        let mut steps = vec![(SerializationStep::Node(self), 0)];
+        while let Some((step, level)) = steps.pop() {
+            if level > 0 || matches!(step, SerializationStep::EndTag(_)) {
+                // every node but the root and every end-tag starts a line: <a> / <b/> / </a>
+                pretty_string.push('\n');
+                pretty_string.push_str(&indent.repeat(level));
+            }
+            match step {
+                SerializationStep::Node(XMLElement::Element(name, attributes, children))
+                    if !children.is_empty()
+                        && !children.iter().any(|child| {
+                            matches!(
+                                child,
+                                XMLElement::Content(_) | XMLElement::Text(_) | XMLElement::Cdata(_)
+                            )
+                        }) =>
+                {
+                    let _ = write!(pretty_string, "<{}", name);
+                    let _ = write_attributes(pretty_string, attributes);
+                    pretty_string.push('>');
+                    steps.push((SerializationStep::EndTag(name), level));
+                    steps.extend(
+                        children
+                            .iter()
+                            .rev()
+                            .map(|child| (SerializationStep::Node(child), level + 1)),
+                    );
+                }
+                // elements with text are written unchanged, as new whitespace between their children would change the text, and so are elements without children
+                SerializationStep::Node(node) => {
+                    let _ = write!(pretty_string, "{}", node);
+                }
+                SerializationStep::EndTag(name) => {
+                    let _ = write!(pretty_string, "</{}>", name);
+                }
+            }
+        }
+    }
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self) -> serde_json::Value {
+        // converts an element into {"name": body}, where the body is an object with
+        // - every attribute under "@name", attributes without a value are null
+        // - the decoded text of the direct contents and CDATA under "#text", if there is any
+        // - every child element under its name, children with the same name are collapsed into an array in document order
+        // comments, doctypes and processing instructions are left out, a content converts into its text and the other nodes into null
+        // the tree is converted without recursion, but serde_json drops a nested value recursively, so the value of a very deep tree is best taken apart from the top
+        match self {
+            XMLElement::Element(name, ..) | XMLElement::EmptyElement(name, _) => {
+                let mut object = serde_json::Map::new();
+                object.insert(name.to_string(), to_json_body(self));
+                serde_json::Value::Object(object)
+            }
+            XMLElement::Content(content) => {
+                serde_json::Value::String(decode_entities(content).into_owned())
+            }
+            XMLElement::Text(text) | XMLElement::Cdata(text) => {
+                serde_json::Value::String(text.to_string())
+            }
+            _ => serde_json::Value::Null,
+        }
+    }
+}
+
+pub struct Descendants<'b, 'a> {
+    stack: Vec<std::slice::Iter<'b, XMLElement<'a>>>, // the remaining children of every ancestor of the next node
+}
+
+impl<'b, 'a> Iterator for Descendants<'b, 'a> {
+    type Item = &'b XMLElement<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // visits all nodes below an element depth-first, so they are returned in document order
+        loop {
+            match self.stack.last_mut()?.next() {
+                Some(child) => {
+                    self.stack.push(child.children().iter());
+                    return Some(child);
+                }
+                None => {
+                    self.stack.pop(); // all children of this node were visited
+                }
+            }
+        }
+    }
+}
+
+pub trait Visitor {
+    // every method does nothing by default, so a visitor only implements the nodes it is interested in
+    fn visit_element(&mut self, _element: &XMLElement) {} // called for elements and empty elements before their children
+    fn visit_text(&mut self, _text: &str) {}
+    fn visit_comment(&mut self, _text: &str) {}
+    fn visit_cdata(&mut self, _text: &str) {}
+}
+
+#[derive(Debug)]
+pub struct LinkedXMLElement {
+    element: OwnedXMLElement, // the node itself, an element keeps its name and attributes but its children are moved to the links below
+    parent: Weak<LinkedXMLElement>, // dangles for the root, as nothing else owns it
+    children: OnceCell<Vec<Rc<LinkedXMLElement>>>, // set once the node is shared, so the children can link to it
+}
+
+impl LinkedXMLElement {
+    fn new(element: OwnedXMLElement, parent: Weak<LinkedXMLElement>) -> Rc<Self> {
+        // links the nodes from a stack of nodes whose children are not linked yet instead of recursion, so the depth of the tree is not limited by the call stack
+        let unlinked = |mut element: OwnedXMLElement, parent| {
+            let children = match &mut element {
+                XMLElement::Element(_, _, children) => std::mem::take(children),
+                _ => Vec::new(),
+            };
+            let node = Rc::new(LinkedXMLElement {
+                element,
+                parent,
+                children: OnceCell::new(),
+            });
+            (node, children)
+        };
+        let (root, children) = unlinked(element, parent);
+        let mut stack = vec![(root.clone(), children)];
+        while let Some((node, children)) = stack.pop() {
+            let links = children
+                .into_iter()
+                .map(|child| {
+                    let (link, grandchildren) = unlinked(child, Rc::downgrade(&node));
+                    stack.push((link.clone(), grandchildren));
+                    link
+                })
+                .collect();
+            let _ = node.children.set(links);
+        }
+        root
+    }
+    pub fn element(&self) -> &OwnedXMLElement {
+        &self.element
+    }
+    pub fn parent(&self) -> Option<Rc<LinkedXMLElement>> {
+        self.parent.upgrade() // None for the root or when the tree above this node was already dropped
+    }
+    pub fn children(&self) -> &[Rc<LinkedXMLElement>] {
+        self.children.get().map_or(&[], Vec::as_slice)
+    }
+}
+
+impl Drop for LinkedXMLElement {
+    fn drop(&mut self) {
+        // like the Drop of XMLElement, the children that are not shared elsewhere are dropped from a stack
+        let mut stack = self.children.take().unwrap_or_default();
+        while let Some(child) = stack.pop() {
+            if let Some(mut child) = Rc::into_inner(child) {
+                stack.extend(child.children.take().unwrap_or_default());
+            }
+        }
+    }
+}
+
+impl OwnedXMLElement {
+    pub fn into_linked_tree(self) -> Rc<LinkedXMLElement> {
+        // links every node to its parent, so the tree can be walked upwards from any node, e.g. to read an attribute of an ancestor
+        LinkedXMLElement::new(self, Weak::new())
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ElementBuilder {
+    name: Cow<'static, str>,
+    attributes: Attributes<'static>,
+    children: Vec<OwnedXMLElement>,
+}
+
+impl ElementBuilder {
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        ElementBuilder {
+            name: name.into(),
+            ..Default::default()
+        }
+    }
+    pub fn attr(
+        mut self,
+        name: impl Into<Cow<'static, str>>,
+        value: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        // setting an attribute again replaces its value
+        // the value is stored escaped, like the raw values of a parsed document, so it reads back unchanged: AT&T -> AT&amp;T
+        let value = Cow::Owned(escape_attr(&value.into()));
+        self.attributes.insert(name.into(), Some(value));
+        self
+    }
+    pub fn child(mut self, element: OwnedXMLElement) -> Self {
+        self.children.push(element);
+        self
+    }
+    pub fn text(mut self, text: impl Into<Cow<'static, str>>) -> Self {
+        self.children.push(XMLElement::Text(text.into())); // the text is escaped when it is written: AT&T -> AT&amp;T
+        self
+    }
+    pub fn build(self) -> OwnedXMLElement {
+        // an element without children is built as an empty element: <name/>
+        if self.children.is_empty() {
+            XMLElement::EmptyElement(self.name, self.attributes)
+        } else {
+            XMLElement::Element(self.name, self.attributes, self.children)
+        }
+    }
+}
+
+fn to_owned_text(text: &str) -> Cow<'static, str> {
+    Cow::Owned(text.to_string())
+}
+fn to_owned_attributes(attributes: &Attributes) -> Attributes<'static> {
+    attributes
+        .iter()
+        .map(|(name, value)| (to_owned_text(name), value.as_deref().map(to_owned_text)))
+        .collect()
+}
+
+#[cfg(feature = "serde_json")]
+struct JsonBody<'b, 'a> {
+    name: &'b str,
+    children: std::slice::Iter<'b, XMLElement<'a>>, // the children that are not converted yet
+    body: serde_json::Map<String, serde_json::Value>,
+    text: String,
+}
+
+#[cfg(feature = "serde_json")]
+impl<'b, 'a> JsonBody<'b, 'a> {
+    fn new(element: &'b XMLElement<'a>) -> Self {
+        let mut body = serde_json::Map::new();
+        for (name, value) in element.attributes().into_iter().flat_map(Attributes::iter) {
+            let value = value
+                .as_ref()
+                .map_or(serde_json::Value::Null, |value| value.to_string().into());
+            body.insert(format!("@{}", name), value);
+        }
+        JsonBody {
+            name: element.name().unwrap_or_default(),
+            children: element.children().iter(),
+            body,
+            text: String::new(),
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+fn to_json_body(element: &XMLElement) -> serde_json::Value {
+    // the bodies of the open elements are kept on a stack and added to their parent once all of their children are converted, instead of recursion
+    let mut stack = vec![JsonBody::new(element)];
+    while let Some(open) = stack.last_mut() {
+        match open.children.next() {
+            Some(XMLElement::Content(content)) => open.text.push_str(&decode_entities(content)),
+            Some(XMLElement::Text(cdata) | XMLElement::Cdata(cdata)) => open.text.push_str(cdata),
+            Some(child @ (XMLElement::Element(..) | XMLElement::EmptyElement(..))) => {
+                stack.push(JsonBody::new(child));
+            }
+            Some(_) => {}
+            None => {
+                let Some(JsonBody {
+                    name,
+                    mut body,
+                    text,
+                    ..
+                }) = stack.pop()
+                else {
+                    unreachable!("the stack was not empty");
+                };
+                if !text.is_empty() {
+                    body.insert("#text".to_string(), text.into());
+                }
+                let child_body = serde_json::Value::Object(body);
+                let Some(parent) = stack.last_mut() else {
+                    return child_body; // the body of the given element
+                };
+                match parent.body.get_mut(name) {
+                    Some(serde_json::Value::Array(repeated)) => repeated.push(child_body),
+                    Some(previous) => *previous = vec![previous.take(), child_body].into(),
+                    None => {
+                        parent.body.insert(name.to_string(), child_body);
+                    }
+                }
+            }
+        }
+    }
+    unreachable!("the body of the given element is returned when its stack entry is closed")
+}
+
+fn write_attributes(f: &mut impl fmt::Write, attributes: &Attributes) -> fmt::Result {
+    for (name, value) in attributes.iter() {
+        let Some(value) = value else {
+            write!(f, " {}", name)?; // an attribute without a value
+            continue;
+        };
+        let value = encode_entities(value);
+        if !value.contains('"') {
+            write!(f, " {}=\"{}\"", name, value)?;
+        } else if !value.contains('\'') {
+            // values that contain double quotes were originally written in single quotes
+            write!(f, " {}='{}'", name, value)?;
+        } else {
+            write!(f, " {}=\"{}\"", name, value.replace('"', "&quot;"))?; // a value can't contain both quotation marks
         }
     }
-    result
+    Ok(())
+}
+
+enum SerializationStep<'b, 'a> {
+    Node(&'b XMLElement<'a>),
+    EndTag(&'b str), // written after all children of an element
+}
+
+impl fmt::Display for XMLElement<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // uses its own stack instead of recursion, so the depth of the tree is not limited by the call stack
+        let mut steps = vec![SerializationStep::Node(self)];
+        while let Some(step) = steps.pop() {
+            let node = match step {
+                SerializationStep::Node(node) => node,
+                SerializationStep::EndTag(name) => {
+                    write!(f, "</{}>", name)?;
+                    continue;
+                }
+            };
+            match node {
+                XMLElement::Element(name, attributes, children) => {
+                    write!(f, "<{}", name)?;
+                    write_attributes(f, attributes)?;
+                    write!(f, ">")?;
+                    steps.push(SerializationStep::EndTag(name));
+                    steps.extend(children.iter().rev().map(SerializationStep::Node));
+                    // reversed, so the first child is written first
+                }
+                XMLElement::EmptyElement(name, attributes) => {
+                    write!(f, "<{}", name)?;
+                    write_attributes(f, attributes)?;
+                    write!(f, "/>")?;
+                }
+                XMLElement::Comment(comment) => write!(f, "<!--{}-->", comment)?,
+                XMLElement::Cdata(cdata) => write!(f, "<![CDATA[{}]]>", cdata)?,
+                XMLElement::Content(content) => write!(f, "{}", encode_entities(content))?,
+                XMLElement::Text(text) => write!(f, "{}", escape_text(text))?, // every '&' is escaped, as the text contains no references
+                XMLElement::Doctype(doctype) => write!(f, "<!DOCTYPE {}>", doctype)?,
+                XMLElement::ProcessingInstruction(target, data) if data.is_empty() => {
+                    write!(f, "<?{}?>", target)?
+                }
+                XMLElement::ProcessingInstruction(target, data) => {
+                    write!(f, "<?{} {}?>", target, data)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Converts a document into its root element.
+///
+/// ```
+/// use xml::{ParseError, XMLElement};
+///
+/// let root: XMLElement = r#"<?xml version="1.0"?><a><b/></a>"#.try_into()?;
+/// assert_eq!(root.to_string(), "<a><b/></a>");
+///
+/// let comment_only: Result<XMLElement, _> = "<!-- c -->".try_into();
+/// assert_eq!(comment_only.err(), Some(ParseError::NoRootElement));
+///
+/// // a Vec can't implement TryFrom<&str> in this crate, parse returns every top-level node instead
+/// let tree: Vec<XMLElement> = xml::parse("<!-- c --><a/>")?;
+/// assert_eq!(tree.len(), 2);
+/// # Ok::<(), ParseError>(())
+/// ```
+impl<'a> TryFrom<&'a str> for XMLElement<'a> {
+    type Error = ParseError;
+
+    fn try_from(raw_xml: &'a str) -> Result<Self, Self::Error> {
+        // parses a document into its root element, skipping the declaration, comments and any other top-level node
+        let (_, nodes) = parse_document(raw_xml)?;
+        nodes
+            .into_iter()
+            .find(|node| node.name().is_some())
+            .ok_or(ParseError::NoRootElement)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Attributes<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // a map from the names to the values in document order, attributes without a value are null
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(Some(self.len()))?;
+        for (name, value) in self.iter() {
+            map.serialize_entry(name, value)?;
+        }
+        map.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for XMLElement<'_> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // every node is a map with its kind under "type": {"type": "element", "name": "a", "attributes": {}, "children": []}
+        use serde::ser::SerializeMap;
+        let mut map = serializer.serialize_map(None)?;
+        match self {
+            XMLElement::Element(name, attributes, children) => {
+                map.serialize_entry("type", "element")?;
+                map.serialize_entry("name", name)?;
+                map.serialize_entry("attributes", attributes)?;
+                map.serialize_entry("children", children)?;
+            }
+            XMLElement::EmptyElement(name, attributes) => {
+                map.serialize_entry("type", "empty_element")?;
+                map.serialize_entry("name", name)?;
+                map.serialize_entry("attributes", attributes)?;
+            }
+            XMLElement::Comment(text) => {
+                map.serialize_entry("type", "comment")?;
+                map.serialize_entry("text", text)?;
+            }
+            XMLElement::Cdata(text) => {
+                map.serialize_entry("type", "cdata")?;
+                map.serialize_entry("text", text)?;
+            }
+            XMLElement::Content(text) => {
+                map.serialize_entry("type", "content")?;
+                map.serialize_entry("text", text)?;
+            }
+            XMLElement::Text(text) => {
+                map.serialize_entry("type", "text")?;
+                map.serialize_entry("text", text)?;
+            }
+            XMLElement::Doctype(text) => {
+                map.serialize_entry("type", "doctype")?;
+                map.serialize_entry("text", text)?;
+            }
+            XMLElement::ProcessingInstruction(target, data) => {
+                map.serialize_entry("type", "processing_instruction")?;
+                map.serialize_entry("target", target)?;
+                map.serialize_entry("data", data)?;
+            }
+        }
+        map.end()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Attributes<'static> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // the map of Serialize, the order of its entries becomes the order of the attributes
+        struct AttributesVisitor;
+        impl<'de> serde::de::Visitor<'de> for AttributesVisitor {
+            type Value = Attributes<'static>;
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a map from attribute names to values")
+            }
+            fn visit_map<M: serde::de::MapAccess<'de>>(
+                self,
+                mut map: M,
+            ) -> Result<Self::Value, M::Error> {
+                let mut attributes = Attributes::new();
+                while let Some((name, value)) = map.next_entry::<String, Option<String>>()? {
+                    attributes.insert(Cow::Owned(name), value.map(Cow::Owned));
+                }
+                Ok(attributes)
+            }
+        }
+        deserializer.deserialize_map(AttributesVisitor)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for OwnedXMLElement {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // the map of Serialize, its entries may come in any order, so they are collected before the node is built
+        struct XMLElementVisitor;
+        impl<'de> serde::de::Visitor<'de> for XMLElementVisitor {
+            type Value = OwnedXMLElement;
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a map with the type of the node")
+            }
+            fn visit_map<M: serde::de::MapAccess<'de>>(
+                self,
+                mut map: M,
+            ) -> Result<Self::Value, M::Error> {
+                use serde::de::Error;
+                let mut kind = None::<String>;
+                let mut name = None::<String>;
+                let mut attributes = None::<Attributes<'static>>;
+                let mut children = None::<Vec<OwnedXMLElement>>;
+                let mut text = None::<String>;
+                let mut target = None::<String>;
+                let mut data = None::<String>;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "type" => kind = Some(map.next_value()?),
+                        "name" => name = Some(map.next_value()?),
+                        "attributes" => attributes = Some(map.next_value()?),
+                        "children" => children = Some(map.next_value()?),
+                        "text" => text = Some(map.next_value()?),
+                        "target" => target = Some(map.next_value()?),
+                        "data" => data = Some(map.next_value()?),
+                        _ => {
+                            map.next_value::<serde::de::IgnoredAny>()?; // unknown keys are skipped
+                        }
+                    }
+                }
+                let required = |value: Option<String>, field: &'static str| {
+                    value
+                        .map(Cow::Owned)
+                        .ok_or_else(|| M::Error::missing_field(field))
+                };
+                let kind = kind.ok_or_else(|| M::Error::missing_field("type"))?;
+                Ok(match kind.as_str() {
+                    "element" => XMLElement::Element(
+                        required(name, "name")?,
+                        attributes.unwrap_or_default(),
+                        children.unwrap_or_default(),
+                    ),
+                    "empty_element" => XMLElement::EmptyElement(
+                        required(name, "name")?,
+                        attributes.unwrap_or_default(),
+                    ),
+                    "comment" => XMLElement::Comment(required(text, "text")?),
+                    "cdata" => XMLElement::Cdata(required(text, "text")?),
+                    "content" => XMLElement::Content(required(text, "text")?),
+                    "text" => XMLElement::Text(required(text, "text")?),
+                    "doctype" => XMLElement::Doctype(required(text, "text")?),
+                    "processing_instruction" => XMLElement::ProcessingInstruction(
+                        required(target, "target")?,
+                        required(data, "data")?,
+                    ),
+                    _ => {
+                        return Err(M::Error::unknown_variant(
+                            &kind,
+                            &[
+                                "element",
+                                "empty_element",
+                                "comment",
+                                "cdata",
+                                "content",
+                                "text",
+                                "doctype",
+                                "processing_instruction",
+                            ],
+                        ))
+                    }
+                })
+            }
+        }
+        deserializer.deserialize_map(XMLElementVisitor)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    // a tag is missing its closing delimiter, e.g. <?xml ... > or </name
+    UnterminatedTag,
+    // an attribute value is missing its closing quotation mark, so the tag ends inside of it, e.g. <a title="oops>
+    UnterminatedAttribute,
+    // a tag or processing instruction has no name, e.g. <>, </> or <? ?>
+    EmptyName,
+    // an attribute is not of the form name="value" or name='value'
+    MalformedAttribute,
+    // the document ended in the middle of a tag
+    UnexpectedEof,
+    // an end-tag has no matching start-tag, e.g. <a></b>
+    UnmatchedEndTag(String),
+    // an attribute appears more than once in the same tag, e.g. <a x="1" x="2"/>
+    DuplicateAttribute(String),
+    // the XML declaration doesn't end with ?>, has no version 1.0 or 1.1, or a standalone other than yes or no
+    MalformedDeclaration,
+    // non-whitespace text comes before the root element in ParseOptions::strict mode, e.g. oops<a/>
+    TextBeforeRoot,
+    // a document has no root element to convert, e.g. <!-- c --> given to XMLElement::try_from
+    NoRootElement,
+    // a processing instruction target starts with xml in a case other than lowercase, which is reserved, e.g. <?XML?> or <?Xml-stylesheet?>
+    ReservedPiTarget(String),
+    // an element is never closed, e.g. <a><b></a> or a document that ends after <a>
+    UnclosedElement(String),
+    // the XML declaration is not the first thing in the document, e.g. <a/><?xml version="1.0"?>
+    MisplacedDeclaration,
+    // more elements are nested than ParseOptions::max_depth allows
+    DepthExceeded,
+    // a tag has more attributes or a longer attribute value than ParseOptions::max_attributes or max_attribute_len allow
+    LimitExceeded,
+    // the XML declaration names an encoding that can't be decoded, e.g. <?xml encoding="EBCDIC"?>
+    UnsupportedEncoding(String),
+    // the bytes of a UTF-16 document are not valid UTF-16, e.g. an unpaired surrogate
+    InvalidEncoding,
+    // the bytes of a UTF-8 document are not valid UTF-8, with the byte offset of the first invalid byte
+    InvalidUtf8 { offset: usize },
+    // a name of an element or attribute contains a character XML doesn't allow there, e.g. <1a> or <a b%="c">
+    InvalidName(String),
+    // a content refers to an entity that is neither predefined nor declared in the DOCTYPE, e.g. &ampp;
+    UnknownEntity(String),
+    // the document couldn't be read from its source, with the message of the I/O error
+    Io(String),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::UnterminatedTag => write!(f, "unterminated tag"),
+            ParseError::UnterminatedAttribute => {
+                write!(f, "unterminated attribute value")
+            }
+            ParseError::EmptyName => write!(f, "missing name in tag"),
+            ParseError::MalformedAttribute => write!(f, "malformed attribute"),
+            ParseError::UnexpectedEof => write!(f, "unexpected end of input"),
+            ParseError::UnmatchedEndTag(name) => write!(f, "unmatched end-tag </{}>", name),
+            ParseError::DuplicateAttribute(name) => write!(f, "duplicate attribute {}", name),
+            ParseError::MalformedDeclaration => write!(f, "malformed XML declaration"),
+            ParseError::TextBeforeRoot => write!(f, "text before the root element"),
+            ParseError::NoRootElement => write!(f, "no root element"),
+            ParseError::ReservedPiTarget(target) => {
+                write!(f, "reserved processing instruction target {}", target)
+            }
+            ParseError::UnclosedElement(name) => write!(f, "unclosed element <{}>", name),
+            ParseError::MisplacedDeclaration => {
+                write!(
+                    f,
+                    "the XML declaration must be at the start of the document"
+                )
+            }
+            ParseError::DepthExceeded => write!(f, "maximum nesting depth exceeded"),
+            ParseError::LimitExceeded => write!(f, "attribute limit exceeded"),
+            ParseError::UnsupportedEncoding(encoding) => {
+                write!(f, "unsupported encoding {}", encoding)
+            }
+            ParseError::InvalidEncoding => write!(f, "invalid byte sequence for the encoding"),
+            ParseError::InvalidUtf8 { offset } => {
+                write!(f, "invalid UTF-8 at byte offset {}", offset)
+            }
+            ParseError::InvalidName(name) => write!(f, "invalid name {}", name),
+            ParseError::UnknownEntity(name) => write!(f, "unknown entity &{};", name),
+            ParseError::Io(message) => write!(f, "failed to read the document: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize, // byte offset of the first character of the element
+    pub end: usize,   // byte offset after the last character of the element
+    pub start_line: usize,
+    pub start_col: usize,
+    pub end_line: usize, // the line and column of the last character of the element
+    pub end_col: usize,
+    pub children: Vec<Span>, // the spans of the children of an element, in the same order as the children
+    pub attributes: Vec<(String, Range<usize>)>, // the byte ranges of the attribute values without their quotation marks, in the same order as the attributes
+}
+
+impl Span {
+    fn new(raw_xml: &str, section: &str) -> Self {
+        let start = section.as_ptr() as usize - raw_xml.as_ptr() as usize; // every section is a slice of the document
+        let mut span = Span::default(); // a Span implements Drop, so it can't be built from the default with ..
+        span.start = start;
+        span.end = start + section.len();
+        span
+    }
+    fn set_attributes(&mut self, raw_xml: &str, attributes: &Attributes) {
+        self.attributes = attributes
+            .iter()
+            .filter_map(|(name, value)| match value {
+                Some(Cow::Borrowed(value)) => {
+                    let value = Span::new(raw_xml, value); // the values are slices of the document, as they are never decoded while parsing
+                    Some((name.to_string(), value.start..value.end))
+                }
+                _ => None, // attributes without a value have no range: <input disabled>
+            })
+            .collect();
+    }
+    pub fn attribute_span(&self, name: &str) -> Option<Range<usize>> {
+        // the range of an attribute value in the document, so it can be replaced without serializing the element again: &raw_xml[range] -> value
+        self.attributes
+            .iter()
+            .find(|(attribute_name, _)| attribute_name == name)
+            .map(|(_, range)| range.clone())
+    }
+    pub fn source<'s>(&self, raw_xml: &'s str) -> &'s str {
+        // the exact text of the element in the document it was parsed from, so it can be copied without serializing it again
+        &raw_xml[self.start..self.end]
+    }
+    fn set_lines_and_columns(&mut self, raw_xml: &str, line_starts: &[usize]) {
+        // converts the byte offsets into lines and columns, both starting at 1
+        // the spans of the descendants are visited from a stack, so a deeply nested document can't overflow the call stack
+        let last_character = |end: usize| {
+            raw_xml[..end]
+                .char_indices()
+                .next_back()
+                .map_or(0, |(index, _)| index)
+        };
+        let mut offsets = Vec::new();
+        let mut stack = vec![&*self];
+        while let Some(span) = stack.pop() {
+            offsets.extend([span.start, last_character(span.end)]);
+            stack.extend(&span.children);
+        }
+        offsets.sort_unstable();
+        offsets.dedup();
+        // the columns are counted from the previous offset on the same line, so a long line is only read once
+        let mut lines_and_columns = Vec::with_capacity(offsets.len());
+        let mut previous = (0, 1, 1); // the offset, line and column of the previous offset
+        for &offset in &offsets {
+            let line = line_starts.partition_point(|line_start| *line_start <= offset);
+            let (from, column) = if previous.1 == line {
+                (previous.0, previous.2)
+            } else {
+                (line_starts[line - 1], 1)
+            };
+            let column = column + raw_xml[from..offset].chars().count();
+            lines_and_columns.push((line, column));
+            previous = (offset, line, column);
+        }
+        let line_and_column =
+            |offset: usize| lines_and_columns[offsets.partition_point(|other| *other < offset)];
+        let mut stack = vec![self];
+        while let Some(span) = stack.pop() {
+            (span.start_line, span.start_col) = line_and_column(span.start);
+            (span.end_line, span.end_col) = line_and_column(last_character(span.end));
+            stack.extend(span.children.iter_mut());
+        }
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        // like the Drop of XMLElement, the spans of the descendants are moved onto a stack before they are dropped
+        let mut stack = std::mem::take(&mut self.children);
+        while let Some(mut span) = stack.pop() {
+            stack.append(&mut span.children);
+        }
+    }
+}
+
+enum XMLParsingSection<'a> {
+    ElementStart(&'a str, Attributes<'a>),
+    ElementStop(&'a str),
+    FinishedElement(XMLElement<'a>),
+    EmptyElement(XMLElement<'a>),
+    Comment(XMLElement<'a>),
+    Cdata(XMLElement<'a>),
+    Doctype(XMLElement<'a>),
+    ProcessingInstruction(XMLElement<'a>),
+    Declaration(XMLElement<'a>, Attributes<'a>), // the processing instruction with the target xml and the attributes read from its data
+    Content(XMLElement<'a>), // a content or, if its entities were decoded, a text
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Declaration<'a> {
+    pub version: Cow<'a, str>,
+    pub encoding: Option<Cow<'a, str>>,
+    pub standalone: Option<bool>, // standalone="yes" or standalone="no"
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ParseOptions {
+    pub decode_entities: bool, // replaces &amp;, &lt;, &gt;, &quot;, &apos; and character references in the contents of an element, which are then stored as XMLElement::Text
+    pub preserve_whitespace: bool, // keeps contents that are only whitespace (spaces, tabs, CR or LF), e.g. the indentation in <pre> blocks
+    pub lenient: bool, // treats a '<' that can't start a tag as part of the content, e.g. <p>1 < 2</p>
+    pub case_insensitive_tags: bool, // lets an end-tag close a start-tag that only differs in ASCII case, e.g. <DIV></div>
+    pub strict: bool, // returns an error for elements that are never closed instead of dropping them, e.g. <a><b></a>, and for text before the root element
+    pub max_depth: Option<usize>, // the most elements that may be open at the same time, protecting against maliciously nested documents
+    pub max_attributes: Option<usize>, // the most attributes a single tag may have
+    pub max_attribute_len: Option<usize>, // the longest attribute value in bytes, before any entities are decoded
+    pub normalize_attr_whitespace: bool, // replaces tabs and line breaks in attribute values with spaces, as the XML specification does: "a\r\n\tb" -> "a  b"
+    pub normalize_tag_names: bool, // converts element names to ASCII lowercase, so <DIV/> is stored and serialized as <div/>, the end-tags are still matched by case_insensitive_tags
+    pub normalize_attr_names: bool, // converts attribute names to ASCII lowercase, so get_attr("href") also finds HREF
+    pub strict_entities: bool, // returns an error for references in contents that are not predefined or declared in the DOCTYPE, e.g. &ampp;
+    pub validate_names: bool, // returns an error for element and attribute names that are not XML names, e.g. <1a> or <a b%="c">
+}
+
+fn is_name_start_char(character: char) -> bool {
+    // the NameStartChar production of the XML specification
+    matches!(character,
+        ':' | 'A'..='Z' | '_' | 'a'..='z'
+        | '\u{C0}'..='\u{D6}' | '\u{D8}'..='\u{F6}' | '\u{F8}'..='\u{2FF}'
+        | '\u{370}'..='\u{37D}' | '\u{37F}'..='\u{1FFF}' | '\u{200C}'..='\u{200D}'
+        | '\u{2070}'..='\u{218F}' | '\u{2C00}'..='\u{2FEF}' | '\u{3001}'..='\u{D7FF}'
+        | '\u{F900}'..='\u{FDCF}' | '\u{FDF0}'..='\u{FFFD}' | '\u{10000}'..='\u{EFFFF}')
+}
+fn is_name_char(character: char) -> bool {
+    // the NameChar production of the XML specification, the characters allowed after the first one of a name
+    is_name_start_char(character)
+        || matches!(character,
+            '-' | '.' | '0'..='9' | '\u{B7}' | '\u{300}'..='\u{36F}' | '\u{203F}'..='\u{2040}')
+}
+fn validate_name(name: &str) -> Result<(), ParseError> {
+    let mut characters = name.chars();
+    if characters.next().is_some_and(is_name_start_char) && characters.all(is_name_char) {
+        Ok(())
+    } else {
+        Err(ParseError::InvalidName(name.to_string()))
+    }
+}
+fn validate_names(name: &str, attributes: &Attributes) -> Result<(), ParseError> {
+    validate_name(name)?;
+    attributes
+        .iter()
+        .try_for_each(|(attribute_name, _)| validate_name(attribute_name))
+}
+
+fn normalize_attribute_whitespace(attributes: &mut Attributes) {
+    for value in attributes
+        .attributes
+        .iter_mut()
+        .filter_map(|(_, value)| value.as_mut())
+    {
+        if value.contains(['\t', '\r', '\n']) {
+            // a line break of CR and LF is a single space, like any other line break
+            *value = Cow::Owned(value.replace("\r\n", "\n").replace(['\t', '\r', '\n'], " "));
+        }
+    }
+}
+fn normalize_name(name: Cow<'_, str>) -> Cow<'_, str> {
+    // converts a name to ASCII lowercase, copying it only if it has an uppercase letter: HREF -> href
+    if name.bytes().any(|byte| byte.is_ascii_uppercase()) {
+        Cow::Owned(name.to_ascii_lowercase())
+    } else {
+        name
+    }
+}
+fn normalize_attribute_names(attributes: &mut Attributes) -> Result<(), ParseError> {
+    let mut normalized_attributes = Attributes::new();
+    for (name, value) in attributes.attributes.drain(..) {
+        let name = normalize_name(name);
+        if normalized_attributes.contains_key(&name) {
+            // names that only differ in case are the same attribute after normalizing: <a ID="1" id="2">
+            return Err(ParseError::DuplicateAttribute(name.to_string()));
+        }
+        normalized_attributes.attributes.push((name, value));
+    }
+    *attributes = normalized_attributes;
+    Ok(())
+}
+fn is_whitespace(character: char) -> bool {
+    matches!(character, ' ' | '\t' | '\r' | '\n') // the whitespace characters allowed by the XML specification
+}
+fn parse_element_name_and_attributes<'a>(
+    raw_xml: &'a str,
+    options: &ParseOptions,
+) -> Result<(&'a str, Attributes<'a>), ParseError> {
+    let raw_xml = raw_xml.trim_end_matches(is_whitespace); // whitespace before the end of the tag: <br /> -> br
+    if raw_xml.is_empty() || raw_xml.starts_with(is_whitespace) {
+        return Err(ParseError::EmptyName); // the name has to follow the '<' directly: <>, < a>
+    }
+    if let Some((name, raw_attributes)) = raw_xml.split_once(is_whitespace)
+    // removes the pre- and suffix as well as split the tag into the name and the attribute list: <name attribute_one="one two" attribute_two="one two"> -> name & attribute_one="one two" attribute_two="one two"
+    {
+        let mut attributes = Attributes::new();
+        let mut remaining_attributes = raw_attributes.trim_start_matches(is_whitespace);
+        while !remaining_attributes.is_empty() {
+            // splits off the name of the next attribute: attribute_one="one two" attribute_two='one two' -> attribute_one & ="one two" attribute_two='one two'
+            let (name, remaining_attribute) = remaining_attributes.split_at(
+                remaining_attributes
+                    .find(|x| x == '=' || is_whitespace(x))
+                    .unwrap_or(remaining_attributes.len()),
+            );
+            if options
+                .max_attributes
+                .is_some_and(|max_attributes| attributes.len() >= max_attributes)
+            {
+                // checked while scanning, so the rest of an abusive tag is never read
+                return Err(ParseError::LimitExceeded);
+            }
+            if name.is_empty() || name.contains(['"', '\'']) {
+                // a quotation mark can only start a value: x"y"="1"
+                return Err(ParseError::MalformedAttribute);
+            }
+            if attributes.contains_key(name) {
+                // well-formed XML does not allow an attribute to appear twice in the same tag
+                return Err(ParseError::DuplicateAttribute(name.to_string()));
+            }
+            let remaining_attribute = remaining_attribute.trim_start_matches(is_whitespace);
+            let Some(quoted_values) = remaining_attribute.strip_prefix('=') else {
+                // the attribute has no value: disabled attribute_two='one two' -> disabled & attribute_two='one two'
+                attributes.insert(Cow::Borrowed(name), None);
+                remaining_attributes = remaining_attribute;
+                continue;
+            };
+            let quoted_values = quoted_values.trim_start_matches(is_whitespace);
+            let quote = quoted_values
+                .chars()
+                .next()
+                .filter(|x| *x == '"' || *x == '\'')
+                .ok_or(ParseError::MalformedAttribute)?;
+            // the value ends at the next quotation mark of the same kind: "one 'two'" attribute_two='one two' -> one 'two' & attribute_two='one two'
+            let (value, update_remaining_attributes) = quoted_values[1..]
+                .split_once(quote)
+                .ok_or(ParseError::UnterminatedAttribute)?; // the quote-aware tokenizer lets the value run to the end of the document
+            if options
+                .max_attribute_len
+                .is_some_and(|max_attribute_len| value.len() > max_attribute_len)
+            {
+                return Err(ParseError::LimitExceeded);
+            }
+            if update_remaining_attributes.starts_with(|x| !is_whitespace(x)) {
+                // attributes are separated by whitespace: x="1"y="2"
+                return Err(ParseError::MalformedAttribute);
+            }
+            attributes.insert(Cow::Borrowed(name), Some(Cow::Borrowed(value)));
+            remaining_attributes = update_remaining_attributes.trim_start_matches(is_whitespace);
+            // any run of spaces, tabs and newlines separates two attributes
+        }
+        return Ok((name, attributes));
+    }
+    Ok((
+        raw_xml, // if the stripped_xml does not contain a whitespace, it is the name of the element and there are no attributes
+        Attributes::new(),
+    ))
+}
+fn strip_delimiters<'a>(
+    raw_xml: &'a str,
+    prefix: &str,
+    suffix: &str,
+) -> Result<&'a str, ParseError> {
+    raw_xml
+        .strip_prefix(prefix)
+        .ok_or(ParseError::UnterminatedTag)?
+        .strip_suffix(suffix)
+        .ok_or_else(|| {
+            if raw_xml.ends_with('>') {
+                ParseError::UnterminatedTag
+            } else if matches!(tag_length(raw_xml), Err(TagState::Quoted(_))) {
+                ParseError::UnterminatedAttribute // the document ended inside of an attribute value: <a title="oops>text
+            } else {
+                ParseError::UnexpectedEof // only the last section of the document can be missing its '>'
+            }
+        })
+}
+fn parse_version<'a>(
+    raw_xml: &'a str,
+    options: &ParseOptions,
+) -> Result<XMLParsingSection<'a>, ParseError> {
+    let stripped_xml = strip_delimiters(raw_xml, "<?", "?>").map_err(|error| match error {
+        ParseError::UnterminatedTag => ParseError::MalformedDeclaration, // the declaration ends with > instead of ?>: <?xml version="1.0">
+        error => error,
+    })?;
+    let (target, attributes) = parse_element_name_and_attributes(stripped_xml, options)?;
+    let data = stripped_xml[target.len()..].trim_start_matches(is_whitespace);
+    // the declaration is kept as it is written, so it is written back as <?xml version="1.0"?> rather than as an element
+    Ok(XMLParsingSection::Declaration(
+        XMLElement::ProcessingInstruction(Cow::Borrowed(target), Cow::Borrowed(data)),
+        attributes,
+    ))
+}
+fn is_declaration(raw_xml: &str) -> bool {
+    // checks for the target xml, even if the declaration is missing its '?': <?xml version="1.0">
+    raw_xml
+        .strip_prefix("<?xml")
+        .is_some_and(|rest| rest.starts_with(|x| is_whitespace(x) || x == '?' || x == '>'))
+}
+fn parse_processing_instruction<'a>(
+    raw_xml: &'a str,
+    options: &ParseOptions,
+) -> Result<XMLParsingSection<'a>, ParseError> {
+    if is_declaration(raw_xml) {
+        return parse_version(raw_xml, options); // the XML declaration is not a processing instruction
+    }
+    let stripped_xml = strip_delimiters(raw_xml, "<?", "?>")?;
+    let (target, data) = stripped_xml
+        .split_once(is_whitespace)
+        .unwrap_or((stripped_xml, ""));
+    // splits the processing instruction into its target and data: <?target data?> -> target & data
+    if target.is_empty() {
+        return Err(ParseError::EmptyName);
+    }
+    if target
+        .get(..3)
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case("xml") && prefix != "xml")
+    {
+        // targets starting with xml in any other case are reserved, so they are neither a declaration nor a processing instruction: <?XML version="1.0"?>
+        return Err(ParseError::ReservedPiTarget(target.to_string()));
+    }
+    Ok(XMLParsingSection::ProcessingInstruction(
+        XMLElement::ProcessingInstruction(
+            Cow::Borrowed(target),
+            Cow::Borrowed(data.trim_start_matches(is_whitespace)),
+        ),
+    ))
+}
+fn parse_element_start_tag<'a>(
+    raw_xml: &'a str,
+    options: &ParseOptions,
+) -> Result<XMLParsingSection<'a>, ParseError> {
+    let stripped_xml = strip_delimiters(raw_xml, "<", ">")?;
+    let (name, attributes) = parse_element_name_and_attributes(stripped_xml, options)?;
+    Ok(XMLParsingSection::ElementStart(name, attributes))
+}
+fn parse_element_stop_tag(raw_xml: &str) -> Result<XMLParsingSection<'_>, ParseError> {
+    let name = strip_delimiters(raw_xml, "</", ">")?.trim_end_matches(is_whitespace);
+    // remove the pre- and suffix of the end-tag as well as any whitespace before the '>': </name > -> name
+    if name.is_empty() || name.starts_with(is_whitespace) {
+        return Err(ParseError::EmptyName);
+    }
+    Ok(XMLParsingSection::ElementStop(name))
+}
+fn parse_empty_element_tag<'a>(
+    raw_xml: &'a str,
+    options: &ParseOptions,
+) -> Result<XMLParsingSection<'a>, ParseError> {
+    let stripped_xml = strip_delimiters(raw_xml, "<", "/>")?;
+    let (name, attributes) = parse_element_name_and_attributes(stripped_xml, options)?;
+    Ok(XMLParsingSection::EmptyElement(XMLElement::EmptyElement(
+        Cow::Borrowed(name),
+        attributes,
+    )))
+}
+fn parse_comment(raw_xml: &str) -> Result<XMLParsingSection<'_>, ParseError> {
+    Ok(XMLParsingSection::Comment(XMLElement::Comment(
+        Cow::Borrowed(strip_delimiters(raw_xml, "<!--", "-->")?),
+    )))
+    // remove the pre- and suffix of the comment, keeping any padding: <!-- comment --> ->  comment
+}
+fn parse_cdata(raw_xml: &str) -> Result<XMLParsingSection<'_>, ParseError> {
+    Ok(XMLParsingSection::Cdata(XMLElement::Cdata(Cow::Borrowed(
+        strip_delimiters(raw_xml, "<![CDATA[", "]]>")?,
+    ))))
+    // remove the pre- and suffix of the CDATA section, its content is literal and never decoded: <![CDATA[&amp;]]> -> &amp;
+}
+fn parse_doctype(raw_xml: &str) -> Result<XMLParsingSection<'_>, ParseError> {
+    Ok(XMLParsingSection::Doctype(XMLElement::Doctype(
+        Cow::Borrowed(strip_delimiters(raw_xml, "<!DOCTYPE", ">")?.trim_matches(is_whitespace)),
+    )))
+    // remove the pre- and suffix of the doctype: <!DOCTYPE name [subset]> -> name [subset]
+}
+
+fn decode_character_reference(code: &str, radix: u32) -> Option<char> {
+    if code.is_empty() || !code.chars().all(|x| x.is_digit(radix)) {
+        // from_str_radix would also accept a leading sign
+        return None;
+    }
+    char::from_u32(u32::from_str_radix(code, radix).ok()?) // out-of-range code points and surrogates are rejected
+}
+fn decode_entity(name: &str) -> Option<char> {
+    if let Some(code) = name.strip_prefix("#x") {
+        return decode_character_reference(code, 16); // &#xA9; -> ©
+    }
+    if let Some(code) = name.strip_prefix('#') {
+        return decode_character_reference(code, 10); // &#169; -> ©
+    }
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        _ => None,
+    }
+}
+pub fn decode_entities(raw_text: &str) -> Cow<'_, str> {
+    decode_entities_with(raw_text, &[])
+}
+pub fn decode_entities_with<'a>(raw_text: &'a str, entities: &[(&str, &str)]) -> Cow<'a, str> {
+    // like decode_entities, but also replaces the given entities, e.g. the ones declared in the DOCTYPE: &foo; -> bar
+    if !raw_text.contains('&') {
+        // nothing to decode, so the text can be borrowed as is
+        return Cow::Borrowed(raw_text);
+    }
+    let mut decoded_text = String::with_capacity(raw_text.len());
+    let mut remaining_text = raw_text;
+    while let Some(index) = remaining_text.find('&') {
+        let (text, entity) = remaining_text.split_at(index);
+        decoded_text.push_str(text);
+        if let Some((name, update_remaining_text)) = split_reference(&entity[1..]) {
+            // &name;rest -> name & rest
+            if let Some(character) = decode_entity(name) {
+                decoded_text.push(character);
+                remaining_text = update_remaining_text;
+                continue;
+            }
+            if let Some((_, value)) = entities
+                .iter()
+                .find(|(entity_name, _)| *entity_name == name)
+            {
+                decoded_text.push_str(&decode_entities(value)); // the value is not searched for declared entities again, so they can't expand endlessly
+                remaining_text = update_remaining_text;
+                continue;
+            }
+        }
+        // unknown entities are kept untouched
+        decoded_text.push('&');
+        remaining_text = &entity[1..];
+    }
+    decoded_text.push_str(remaining_text);
+    Cow::Owned(decoded_text)
+}
+fn find_unknown_entity<'a>(raw_text: &'a str, entities: &[(&str, &str)]) -> Option<&'a str> {
+    // finds the first reference that is neither predefined, a character reference nor declared: &ampp; -> ampp
+    raw_text.match_indices('&').find_map(|(index, _)| {
+        let (name, _) = split_reference(&raw_text[index + 1..])?; // a single '&' is not a reference: a & b
+        let known = decode_entity(name).is_some()
+            || entities.iter().any(|(entity_name, _)| *entity_name == name);
+        (!known).then_some(name)
+    })
+}
+fn parse_entity_declarations(doctype: &str) -> Vec<(&str, &str)> {
+    // collects the internal entities of a DOCTYPE: <!DOCTYPE x [<!ENTITY foo "bar">]> -> [(foo, bar)]
+    // parameter entities like <!ENTITY % foo "bar"> and external entities like <!ENTITY foo SYSTEM "bar.xml"> are skipped
+    let mut entities = Vec::new();
+    let mut remaining_doctype = doctype;
+    while let Some(index) = remaining_doctype.find("<!ENTITY") {
+        let declaration =
+            remaining_doctype[index + "<!ENTITY".len()..].trim_start_matches(is_whitespace);
+        remaining_doctype = declaration;
+        let Some((name, definition)) = declaration.split_once(is_whitespace) else {
+            break;
+        };
+        let definition = definition.trim_start_matches(is_whitespace);
+        let Some(quote) = definition
+            .chars()
+            .next()
+            .filter(|x| name != "%" && (*x == '"' || *x == '\''))
+        else {
+            continue;
+        };
+        let Some((value, _)) = definition[1..].split_once(quote) else {
+            break;
+        };
+        entities.push((name, value));
+    }
+    entities
+}
+pub fn encode_entities(text: &str) -> Cow<'_, str> {
+    // replaces the characters that are not allowed in contents and attribute values: a < b & c -> a &lt; b &amp; c
+    // an '&' that already starts a reference is kept, so contents that were not decoded while parsing are not encoded twice
+    if !text.contains(['&', '<', '>']) {
+        return Cow::Borrowed(text);
+    }
+    let mut encoded_text = String::with_capacity(text.len());
+    for (index, character) in text.char_indices() {
+        match character {
+            '<' => encoded_text.push_str("&lt;"),
+            '>' => encoded_text.push_str("&gt;"),
+            '&' if !starts_reference(&text[index + 1..]) => encoded_text.push_str("&amp;"),
+            _ => encoded_text.push(character),
+        }
+    }
+    Cow::Owned(encoded_text)
+}
+pub fn escape_text(text: &str) -> String {
+    // escapes every '&', '<' and '>', even one that looks like a reference, so any string can be embedded as a content: a & b < c -> a &amp; b &lt; c
+    let mut escaped_text = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => escaped_text.push_str("&amp;"),
+            '<' => escaped_text.push_str("&lt;"),
+            '>' => escaped_text.push_str("&gt;"),
+            _ => escaped_text.push(character),
+        }
+    }
+    escaped_text
+}
+pub fn escape_attr(value: &str) -> String {
+    // escapes like escape_text and also both quotation marks, so the value can be embedded in single or double quotes: say "hi" -> say &quot;hi&quot;
+    let mut escaped_value = String::with_capacity(value.len());
+    for character in escape_text(value).chars() {
+        match character {
+            '"' => escaped_value.push_str("&quot;"),
+            '\'' => escaped_value.push_str("&apos;"),
+            _ => escaped_value.push(character),
+        }
+    }
+    escaped_value
+}
+fn split_reference(text: &str) -> Option<(&str, &str)> {
+    // splits the rest of an entity or character reference after its '&' into its name and the following text: amp;rest -> amp & rest
+    // only the characters of the name are scanned, so texts with many '&' are still read in linear time
+    let length = text
+        .find(|x: char| !(x.is_alphanumeric() || matches!(x, '#' | '_' | '-' | '.' | ':')))
+        .unwrap_or(text.len());
+    let rest = text[length..].strip_prefix(';')?;
+    (length > 0).then(|| (&text[..length], rest))
+}
+fn starts_reference(text: &str) -> bool {
+    // checks for the rest of an entity or character reference after its '&': amp; or #169; -> true
+    split_reference(text).is_some()
+}
+fn is_omitted_content(raw_xml: &str, preserve_whitespace: bool) -> bool {
+    !preserve_whitespace
+        && raw_xml
+            .chars()
+            .all(|x| matches!(x, '\n' | '\r' | '\t' | ' '))
+    // if the content is only newlines, carriage returns, tabs or spaces, it can be omitted
+}
+fn preserves_space(attributes: &Attributes, parent: Option<bool>, options: &ParseOptions) -> bool {
+    // whether whitespace-only contents are kept in an element, which xml:space="preserve" or "default" sets for the element and its descendants
+    match attributes.get("xml:space").and_then(Option::as_deref) {
+        Some("preserve") => true,
+        Some("default") => options.preserve_whitespace,
+        _ => parent.unwrap_or(options.preserve_whitespace), // inherited from the parent element
+    }
+}
+fn parse_content<'a>(
+    raw_xml: &'a str,
+    options: &ParseOptions,
+    entities: &[(&str, &str)],
+) -> Result<XMLParsingSection<'a>, ParseError> {
+    if options.strict_entities {
+        if let Some(name) = find_unknown_entity(raw_xml, entities) {
+            return Err(ParseError::UnknownEntity(name.to_string()));
+        }
+    }
+    if options.decode_entities {
+        Ok(XMLParsingSection::Content(XMLElement::Text(
+            decode_entities_with(raw_xml, entities),
+        )))
+    } else {
+        Ok(XMLParsingSection::Content(XMLElement::Content(
+            Cow::Borrowed(raw_xml),
+        )))
+    }
+}
+
+struct XMLSections<'a> {
+    remaining_xml: &'a str,
+    lenient: bool, // see ParseOptions::lenient
+}
+
+impl<'a> XMLSections<'a> {
+    fn new(raw_xml: &'a str, lenient: bool) -> Self {
+        XMLSections {
+            remaining_xml: raw_xml.strip_prefix('\u{FEFF}').unwrap_or(raw_xml), // a byte order mark is not part of the document
+            lenient,
+        }
+    }
+}
+
+fn find_tag_start(raw_xml: &str, lenient: bool) -> Option<usize> {
+    // finds the '<' of the next tag, in lenient mode skipping every '<' that is not followed by a name, '/', '!' or '?': 1 < 2</p> -> </p>
+    raw_xml
+        .match_indices('<')
+        .map(|(index, _)| index)
+        .find(|index| {
+            !lenient
+                || raw_xml[index + 1..].starts_with(|x: char| {
+                    x.is_alphabetic() || matches!(x, '_' | ':' | '/' | '!' | '?')
+                })
+        })
+}
+
+enum TagState {
+    Markup,                 // between the names, attributes and brackets of a tag
+    Quoted(u8),             // inside an attribute or entity value, until the same quotation mark
+    Skipped { end: usize }, // inside a comment or processing instruction of a DOCTYPE internal subset, until the index of its closing >
+}
+
+fn tag_length(tag: &str) -> Result<usize, TagState> {
+    // finds the '>' that closes the tag at the start of the given xml, or returns the state the scan was in when the document ended
+    if let Some(comment) = tag.strip_prefix("<!--") {
+        // comments are read as a unit up to the first -->, so they can contain any other character: <!-- 1 > 0 -->
+        return comment
+            .find("-->")
+            .map(|index| "<!--".len() + index + "-->".len())
+            .ok_or(TagState::Markup);
+    }
+    if let Some(cdata) = tag.strip_prefix("<![CDATA[") {
+        // CDATA is read as a unit up to the first ]]>, so it can contain tags and single brackets: <![CDATA[<a>]]]>
+        return cdata
+            .find("]]>")
+            .map(|index| "<![CDATA[".len() + index + "]]>".len())
+            .ok_or(TagState::Markup);
+    }
+    if tag.starts_with("<?") && !is_declaration(tag) {
+        // processing instructions are read as a unit up to the first ?>, as their data is not made of attributes: <?php echo 1 > 0; ?>
+        return tag
+            .find("?>")
+            .map(|index| index + "?>".len())
+            .ok_or(TagState::Markup);
+    }
+    // the special characters are all ASCII, so the bytes can be scanned without splitting a multi-byte character
+    let doctype = tag.starts_with("<!DOCTYPE");
+    let mut state = TagState::Markup;
+    let mut subset_depth = 0; // the internal subset of a DOCTYPE is enclosed in brackets and contains its own tags: <!DOCTYPE x [<!ENTITY y "z">]>
+    let mut markup_depth = 0; // the number of open declarations inside the subset, counted instead of scanned recursively, so deeply nested input can't overflow the stack
+    for (index, byte) in tag.bytes().enumerate() {
+        match state {
+            TagState::Quoted(quote) if byte == quote => state = TagState::Markup,
+            TagState::Quoted(_) => {} // a '>' inside an attribute value does not close the tag
+            TagState::Skipped { end } if index == end => state = TagState::Markup,
+            TagState::Skipped { .. } => {}
+            TagState::Markup => match byte {
+                b'>' if markup_depth > 0 => markup_depth -= 1,
+                b'>' if subset_depth == 0 => return Ok(index + 1),
+                b'"' | b'\'' => state = TagState::Quoted(byte),
+                b'[' if doctype && markup_depth == 0 => subset_depth += 1,
+                b']' if doctype && markup_depth == 0 => subset_depth -= 1,
+                b'<' if subset_depth > 0 => {
+                    let markup = &tag[index..];
+                    // a comment or processing instruction in the subset may contain quotation marks and '>': <!DOCTYPE x [<!-- it's -->]>
+                    let skipped_length = if let Some(comment) = markup.strip_prefix("<!--") {
+                        Some(
+                            "<!--".len()
+                                + comment.find("-->").ok_or(TagState::Markup)?
+                                + "-->".len(),
+                        )
+                    } else if markup.starts_with("<?") {
+                        Some(markup.find("?>").ok_or(TagState::Markup)? + "?>".len())
+                    } else {
+                        None
+                    };
+                    match skipped_length {
+                        Some(length) => {
+                            state = TagState::Skipped {
+                                end: index + length - 1,
+                            }
+                        }
+                        None => markup_depth += 1, // a declaration like <!ENTITY y "z">, closed by the next '>' outside of quotes
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+    Err(state)
+}
+
+impl<'a> Iterator for XMLSections<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        // splits the document after every '>' that closes a tag, so a section is any content followed by one tag: content<name attribute="a > b">
+        if self.remaining_xml.is_empty() {
+            return None;
+        }
+        let tag_start =
+            find_tag_start(self.remaining_xml, self.lenient).unwrap_or(self.remaining_xml.len());
+        let tag = &self.remaining_xml[tag_start..];
+        let section_end =
+            tag_length(tag).map_or(self.remaining_xml.len(), |length| tag_start + length); // if the tag is never closed, the rest of the document is the last section
+        let (section, remaining_xml) = self.remaining_xml.split_at(section_end);
+        self.remaining_xml = remaining_xml;
+        Some(section)
+    }
+}
+
+pub fn parse(raw_xml: &str) -> Result<Vec<XMLElement<'_>>, ParseError> {
+    parse_with_options(raw_xml, &ParseOptions::default())
+}
+
+pub fn parse_owned(raw_xml: &str) -> Result<Vec<OwnedXMLElement>, ParseError> {
+    // parses and copies the document in one step, so the result doesn't borrow the given xml
+    Ok(parse(raw_xml)?.iter().map(XMLElement::to_owned).collect())
+}
+
+pub fn parse_declaration(raw_xml: &str) -> Result<Option<Declaration<'_>>, ParseError> {
+    // reads the XML declaration at the start of a document without parsing the rest: <?xml version="1.0" encoding="UTF-8"?>
+    let raw_xml = raw_xml
+        .trim_start_matches('\u{FEFF}')
+        .trim_start_matches(is_whitespace);
+    if !is_declaration(raw_xml) {
+        return Ok(None);
+    }
+    let declaration = &raw_xml[..tag_length(raw_xml).unwrap_or(raw_xml.len())];
+    let XMLParsingSection::Declaration(_, attributes) =
+        parse_version(declaration, &ParseOptions::default())?
+    else {
+        unreachable!("parse_version only returns declarations");
+    };
+    let version = match attributes.get("version") {
+        Some(Some(version)) if version == "1.0" || version == "1.1" => version.clone(),
+        _ => return Err(ParseError::MalformedDeclaration),
+    };
+    let standalone = match attributes.get("standalone") {
+        None => None,
+        Some(Some(standalone)) if standalone == "yes" => Some(true),
+        Some(Some(standalone)) if standalone == "no" => Some(false),
+        Some(_) => return Err(ParseError::MalformedDeclaration),
+    };
+    Ok(Some(Declaration {
+        version,
+        encoding: attributes.get("encoding").cloned().flatten(),
+        standalone,
+    }))
+}
+
+pub fn parse_document(
+    raw_xml: &str,
+) -> Result<(Option<Declaration<'_>>, Vec<XMLElement<'_>>), ParseError> {
+    // like parse, but the XML declaration is returned separately from the nodes of the document
+    let declaration = parse_declaration(raw_xml)?;
+    let mut nodes = parse(raw_xml)?;
+    if declaration.is_some() {
+        nodes.remove(0); // the declaration is always the first node
+    }
+    Ok((declaration, nodes))
+}
+
+pub fn parse_with_options<'a>(
+    raw_xml: &'a str,
+    options: &ParseOptions,
+) -> Result<Vec<XMLElement<'a>>, ParseError> {
+    let mut result = Vec::new();
+    Parser::with_options(options).parse_into(raw_xml, &mut result)?;
+    Ok(result)
+}
+
+pub fn parse_with_spans(raw_xml: &str) -> Result<Vec<(XMLElement<'_>, Span)>, ParseError> {
+    let mut result = Vec::new();
+    parse_sections(
+        raw_xml,
+        &ParseOptions::default(),
+        true,
+        &mut Vec::new(),
+        &mut result,
+    )?;
+    let line_starts = std::iter::once(0)
+        .chain(raw_xml.match_indices('\n').map(|(index, _)| index + 1))
+        .collect::<Vec<usize>>();
+    for (_, span) in &mut result {
+        span.set_lines_and_columns(raw_xml, &line_starts);
+    }
+    Ok(result)
+}
+
+pub fn parse_bytes(raw_xml: &[u8]) -> Result<Vec<OwnedXMLElement>, ParseError> {
+    // decodes the bytes by their byte order mark or the encoding of the XML declaration and parses the resulting text
+    parse_owned(&decode_bytes(raw_xml)?)
+}
+
+pub fn parse_reader<R: Read>(mut reader: R) -> Result<Vec<OwnedXMLElement>, ParseError> {
+    // reads the whole source, e.g. a file or a socket, before parsing it, as the parser needs the complete document
+    let mut raw_xml = Vec::new();
+    reader
+        .read_to_end(&mut raw_xml)
+        .map_err(|error| ParseError::Io(error.to_string()))?;
+    parse_bytes(&raw_xml)
+}
+
+fn decode_bytes(raw_xml: &[u8]) -> Result<Cow<'_, str>, ParseError> {
+    match raw_xml {
+        [0xEF, 0xBB, 0xBF, ..] => return decode_utf8(raw_xml), // the parser skips the byte order mark, so offsets of errors count it
+        [0xFF, 0xFE, utf16 @ ..] => return decode_utf16(utf16, u16::from_le_bytes),
+        [0xFE, 0xFF, utf16 @ ..] => return decode_utf16(utf16, u16::from_be_bytes),
+        [b'<', 0, b'?', 0, ..] => return decode_utf16(raw_xml, u16::from_le_bytes),
+        [0, b'<', 0, b'?', ..] => return decode_utf16(raw_xml, u16::from_be_bytes),
+        _ => {} // UTF-16 without a byte order mark is recognized by the zero bytes of <?
+    }
+    // without a byte order mark the declaration is readable as ASCII in all supported encodings
+    let Some(encoding) = declared_encoding(raw_xml) else {
+        return decode_utf8(raw_xml);
+    };
+    match encoding.to_ascii_lowercase().as_str() {
+        "utf-8" | "utf8" | "us-ascii" | "ascii" => decode_utf8(raw_xml),
+        "iso-8859-1" | "latin1" | "latin-1" | "l1" => {
+            Ok(Cow::Owned(
+                raw_xml.iter().map(|&byte| byte as char).collect(),
+            ))
+            // every byte of Latin-1 is the unicode character with the same number
+        }
+        _ => Err(ParseError::UnsupportedEncoding(encoding)),
+    }
+}
+
+fn decode_utf8(raw_xml: &[u8]) -> Result<Cow<'_, str>, ParseError> {
+    std::str::from_utf8(raw_xml)
+        .map(Cow::Borrowed)
+        .map_err(|error| ParseError::InvalidUtf8 {
+            offset: error.valid_up_to(),
+        })
+}
+
+fn decode_utf16(
+    raw_xml: &[u8],
+    from_bytes: fn([u8; 2]) -> u16,
+) -> Result<Cow<'_, str>, ParseError> {
+    if !raw_xml.len().is_multiple_of(2) {
+        return Err(ParseError::InvalidEncoding); // UTF-16 consists of pairs of bytes
+    }
+    let code_units = raw_xml
+        .chunks_exact(2)
+        .map(|pair| from_bytes([pair[0], pair[1]]));
+    char::decode_utf16(code_units)
+        .collect::<Result<String, _>>()
+        .map(Cow::Owned)
+        .map_err(|_| ParseError::InvalidEncoding)
+}
+
+fn declared_encoding(raw_xml: &[u8]) -> Option<String> {
+    // reads the encoding attribute of the XML declaration: <?xml version="1.0" encoding="ISO-8859-1"?> -> ISO-8859-1
+    let end = raw_xml.windows(2).position(|window| window == b"?>")?;
+    let declaration = String::from_utf8_lossy(&raw_xml[..end + 2]);
+    let declaration = declaration.trim_start_matches(is_whitespace);
+    if !declaration.starts_with("<?xml") {
+        return None;
+    }
+    let stripped_xml = strip_delimiters(declaration, "<?", "?>").ok()?;
+    let (_, attributes) =
+        parse_element_name_and_attributes(stripped_xml, &ParseOptions::default()).ok()?;
+    attributes.get("encoding")?.as_deref().map(str::to_string)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ParseStats {
+    pub bytes: usize,
+    pub elements: usize, // elements with a start- and end-tag as well as empty elements, but not the XML declaration
+    pub attributes: usize,
+    pub text_nodes: usize,
+    pub max_depth: usize, // the nesting of the deepest element, a root element has a depth of 1
+}
+
+impl ParseStats {
+    fn count(raw_xml: &str, nodes: &[XMLElement]) -> Self {
+        // counts the nodes of the finished tree, so elements that were never closed and texts that were merged are counted as they are returned: <a>1<b>2</a> -> 1 element and 1 text
+        let mut stats = ParseStats {
+            bytes: raw_xml.len(),
+            ..ParseStats::default()
+        };
+        let mut stack: Vec<_> = nodes.iter().map(|node| (node, 1)).collect(); // the nodes with their depth, visited without recursion
+        while let Some((node, depth)) = stack.pop() {
+            match node {
+                XMLElement::Element(_, attributes, _) | XMLElement::EmptyElement(_, attributes) => {
+                    stats.elements += 1;
+                    stats.attributes += attributes.len();
+                    stats.max_depth = stats.max_depth.max(depth);
+                    stack.extend(node.children().iter().map(|child| (child, depth + 1)));
+                }
+                XMLElement::Content(_) | XMLElement::Text(_) => stats.text_nodes += 1,
+                _ => {}
+            }
+        }
+        stats
+    }
+}
+
+pub fn parse_with_stats(raw_xml: &str) -> Result<(Vec<XMLElement<'_>>, ParseStats), ParseError> {
+    // counts the nodes of the parsed document, e.g. to reject unusually large documents
+    let nodes = parse(raw_xml)?;
+    let stats = ParseStats::count(raw_xml, &nodes);
+    Ok((nodes, stats))
+}
+
+pub struct Parser {
+    options: ParseOptions,
+    // kept between documents while empty, so their allocations are reused by documents with any lifetime
+    section_stack: Vec<(XMLParsingSection<'static>, Span)>,
+    parsed: Vec<(XMLElement<'static>, Span)>,
+    result: Vec<XMLElement<'static>>, // the vector of a result given back with recycle
+}
+
+fn recycle_buffer<T, U>(mut buffer: Vec<T>) -> Vec<U> {
+    // empties a buffer and turns it into one for another lifetime of the same type, the standard library reuses the allocation for this
+    buffer.clear();
+    buffer.into_iter().map(|_| unreachable!()).collect()
+}
+
+impl Parser {
+    pub fn new() -> Self {
+        Parser::with_options(&ParseOptions::default())
+    }
+    pub fn with_options(options: &ParseOptions) -> Self {
+        Parser {
+            options: options.clone(),
+            section_stack: Vec::new(),
+            parsed: Vec::new(),
+            result: Vec::new(),
+        }
+    }
+    pub fn parse_into<'a>(
+        &mut self,
+        raw_xml: &'a str,
+        result: &mut Vec<XMLElement<'a>>,
+    ) -> Result<(), ParseError> {
+        // like parse_with_options, but the result replaces the contents of the given vector, so parsing many documents reuses the same allocations
+        let mut section_stack = recycle_buffer(std::mem::take(&mut self.section_stack));
+        let mut parsed = recycle_buffer(std::mem::take(&mut self.parsed));
+        let parsing = parse_sections(
+            raw_xml,
+            &self.options,
+            false,
+            &mut section_stack,
+            &mut parsed,
+        );
+        if parsing.is_ok() {
+            result.clear();
+            result.extend(parsed.drain(..).map(|(element, _)| element));
+        }
+        self.section_stack = recycle_buffer(section_stack);
+        self.parsed = recycle_buffer(parsed);
+        parsing
+    }
+    pub fn parse<'a>(&mut self, raw_xml: &'a str) -> Result<Vec<XMLElement<'a>>, ParseError> {
+        // like parse_into, but into the vector of a result that was given back with recycle, so a result doesn't have to outlive its document
+        let mut result = recycle_buffer(std::mem::take(&mut self.result));
+        self.parse_into(raw_xml, &mut result)?;
+        Ok(result)
+    }
+    pub fn recycle(&mut self, result: Vec<XMLElement<'_>>) {
+        // keeps the allocation of a result for the next call of parse, its nodes are dropped
+        self.result = recycle_buffer(result);
+    }
+}
+
+impl Default for Parser {
+    fn default() -> Self {
+        Parser::new()
+    }
+}
+
+fn parse_sections<'a>(
+    raw_xml: &'a str,
+    options: &ParseOptions,
+    track_spans: bool,
+    section_stack: &mut Vec<(XMLParsingSection<'a>, Span)>,
+    result: &mut Vec<(XMLElement<'a>, Span)>,
+) -> Result<(), ParseError> {
+    section_stack.clear(); // a previous document may have failed to parse
+    result.clear();
+    let mut depth = 0; // the number of start-tags on the stack
+    let mut preserved_spaces = Vec::new(); // whether whitespace is kept in each open element, which xml:space="preserve" or "default" can change for an element and its descendants
+    let mut entities = Vec::new(); // the entities declared in the DOCTYPE
+    let mut seen_root = false; // whether an element was started, after which text at the top level comes after the root
+    for mut section in XMLSections::new(raw_xml, options.lenient) {
+        let preserve_whitespace = preserved_spaces.last() == Some(&true); // whitespace outside of any element is never kept
+        let tag_start = find_tag_start(section, options.lenient).unwrap_or(section.len());
+        if tag_start > 0 {
+            // the content is pushed before its tag is handled, so it belongs to the element that is open after the previous section: <a><b>x</b>y</a> -> y is a child of a
+            let (content, update_section) = section.split_at(tag_start);
+            section = update_section; // the section is empty if it is only content
+            if !is_omitted_content(content, preserve_whitespace) {
+                let span = Span::new(raw_xml, content);
+                if options.strict && !seen_root {
+                    // only whitespace, comments and processing instructions may come before the root element
+                    return Err(ParseError::TextBeforeRoot);
+                }
+                match parse_content(content, options, &entities)? {
+                    XMLParsingSection::Content(content) if depth == 0 => {
+                        // text outside of any element, e.g. between the roots of a fragment
+                        result.push((content, span));
+                    }
+                    content => section_stack.push((content, span)),
+                }
+            }
+        }
+        let mut span = Span::new(raw_xml, section);
+        if section.starts_with("<!--") {
+            // comment
+            if depth == 0 {
+                // there is currently no parent element
+                if let XMLParsingSection::Comment(element) = parse_comment(section)? {
+                    result.push((element, span));
+                }
+            } else {
+                section_stack.push((parse_comment(section)?, span));
+            }
+        } else if section.starts_with("<![CDATA[") {
+            // CDATA
+            if depth == 0 {
+                // there is currently no parent element
+                if let XMLParsingSection::Cdata(element) = parse_cdata(section)? {
+                    result.push((element, span));
+                }
+            } else {
+                section_stack.push((parse_cdata(section)?, span));
+            }
+        } else if section.starts_with("<!DOCTYPE") {
+            // doctype
+            entities = parse_entity_declarations(section);
+            if depth == 0 {
+                // there is currently no parent element
+                if let XMLParsingSection::Doctype(element) = parse_doctype(section)? {
+                    result.push((element, span));
+                }
+            } else {
+                section_stack.push((parse_doctype(section)?, span));
+            }
+        } else if section.starts_with("<?") {
+            // processing instruction
+            let processing_instruction = parse_processing_instruction(section, options)?;
+            if let XMLParsingSection::Declaration(element, _) = processing_instruction {
+                // the XML declaration
+                if !result.is_empty() || !section_stack.is_empty() {
+                    // only whitespace may come before the declaration, not even a comment: <!-- license --><?xml version="1.0"?>
+                    return Err(ParseError::MisplacedDeclaration);
+                }
+                result.push((element, span))
+            } else if depth == 0 {
+                // there is currently no parent element
+                if let XMLParsingSection::ProcessingInstruction(element) = processing_instruction {
+                    result.push((element, span));
+                }
+            } else {
+                section_stack.push((processing_instruction, span));
+            }
+        } else if section.starts_with("</") {
+            // end-tag
+            if let XMLParsingSection::ElementStop(parent_name) = parse_element_stop_tag(section)? {
+                if options.validate_names {
+                    validate_name(parent_name)?;
+                }
+                let mut children = Vec::<XMLElement>::new();
+                let mut child_spans = Vec::<Span>::new();
+                loop {
+                    let Some((section, child_span)) = section_stack.pop() else {
+                        // the stack ran empty without finding the start-tag of this end-tag
+                        return Err(ParseError::UnmatchedEndTag(parent_name.to_string()));
+                    };
+                    let child = match section {
+                        XMLParsingSection::ElementStart(name, attributes) => {
+                            depth -= 1;
+                            preserved_spaces.pop();
+                            if name == parent_name
+                                || options.case_insensitive_tags
+                                    && name.eq_ignore_ascii_case(parent_name)
+                            {
+                                // the start tag of the stop tag was found -> end the parsing of this element
+                                children.reverse(); // as they are added in reverse order, they have to be inversed again
+                                child_spans.reverse();
+                                let name = if options.normalize_tag_names {
+                                    normalize_name(Cow::Borrowed(name))
+                                } else {
+                                    Cow::Borrowed(name)
+                                };
+                                let element = XMLElement::Element(name, attributes, children);
+                                let mut child_span = child_span;
+                                child_span.end = span.end; // the element ends with its end-tag
+                                child_span.children = child_spans;
+                                let span = child_span;
+                                if depth == 0 {
+                                    // a root element is added to the result right away, so it keeps its place among the other top-level nodes
+                                    result.push((element, span));
+                                } else {
+                                    section_stack
+                                        .push((XMLParsingSection::FinishedElement(element), span));
+                                }
+                                break;
+                            }
+                            if options.strict {
+                                // the end-tag belongs to an ancestor, so this element is never closed
+                                return Err(ParseError::UnclosedElement(name.to_string()));
+                            }
+                            continue;
+                        }
+                        XMLParsingSection::ElementStop(_)
+                        | XMLParsingSection::Declaration(_, _) => {
+                            // this should never happen
+                            continue;
+                        }
+                        XMLParsingSection::FinishedElement(element) => element,
+                        XMLParsingSection::EmptyElement(element) => element,
+                        XMLParsingSection::Comment(element) => element,
+                        XMLParsingSection::Cdata(element) => element,
+                        XMLParsingSection::Doctype(element) => element,
+                        XMLParsingSection::ProcessingInstruction(element) => element,
+                        XMLParsingSection::Content(content) => content,
+                    };
+                    if let (
+                        XMLElement::Content(content) | XMLElement::Text(content),
+                        Some(XMLElement::Content(next_content) | XMLElement::Text(next_content)),
+                    ) = (&child, children.last_mut())
+                    {
+                        // the text around a dropped unclosed element is one run of text: <a>1<b>2</a> -> <a>12</a>
+                        *next_content = Cow::Owned(format!("{content}{next_content}"));
+                        if let Some(next_span) = child_spans.last_mut() {
+                            next_span.start = child_span.start;
+                        }
+                        continue;
+                    }
+                    children.push(child);
+                    if track_spans {
+                        child_spans.push(child_span);
+                    }
+                }
+            }
+        } else if section.ends_with("/>") {
+            // empty-element tag
+            seen_root = true;
+            let mut empty_element = parse_empty_element_tag(section, options)?;
+            if let XMLParsingSection::EmptyElement(XMLElement::EmptyElement(name, attributes)) =
+                &mut empty_element
+            {
+                if options.normalize_tag_names {
+                    *name = normalize_name(std::mem::take(name));
+                }
+                if options.normalize_attr_names {
+                    normalize_attribute_names(attributes)?;
+                }
+                if options.validate_names {
+                    validate_names(name, attributes)?;
+                }
+                if track_spans {
+                    span.set_attributes(raw_xml, attributes);
+                }
+                if options.normalize_attr_whitespace {
+                    normalize_attribute_whitespace(attributes); // after the spans, as they need the values that were not copied
+                }
+            }
+            if depth == 0 {
+                // there is currently no parent element
+                if let XMLParsingSection::EmptyElement(element) = empty_element {
+                    result.push((element, span));
+                }
+            } else {
+                section_stack.push((empty_element, span));
+            }
+        } else if section.starts_with('<') {
+            // start-tag
+            seen_root = true;
+            depth += 1;
+            if options.max_depth.is_some_and(|max_depth| depth > max_depth) {
+                return Err(ParseError::DepthExceeded);
+            }
+            let mut start_tag = parse_element_start_tag(section, options)?;
+            if let XMLParsingSection::ElementStart(name, attributes) = &mut start_tag {
+                if options.normalize_attr_names {
+                    normalize_attribute_names(attributes)?;
+                }
+                if options.validate_names {
+                    validate_names(name, attributes)?;
+                }
+                if track_spans {
+                    span.set_attributes(raw_xml, attributes);
+                }
+                if options.normalize_attr_whitespace {
+                    normalize_attribute_whitespace(attributes); // after the spans, as they need the values that were not copied
+                }
+                preserved_spaces.push(preserves_space(
+                    attributes,
+                    preserved_spaces.last().copied(),
+                    options,
+                ));
+            }
+            section_stack.push((start_tag, span)); // always push to stack to make it the current parent element
+        }
+    }
+    for (element, span) in section_stack.drain(..) {
+        // adding the remaining nodes of elements that were never closed to the result, in document order
+        match element {
+            XMLParsingSection::ElementStart(name, _) if options.strict => {
+                return Err(ParseError::UnclosedElement(name.to_string()));
+            }
+            XMLParsingSection::ElementStart(_, _)
+            | XMLParsingSection::ElementStop(_)
+            | XMLParsingSection::Declaration(_, _) => {}
+            XMLParsingSection::FinishedElement(element)
+            | XMLParsingSection::EmptyElement(element)
+            | XMLParsingSection::Comment(element)
+            | XMLParsingSection::Cdata(element)
+            | XMLParsingSection::Doctype(element)
+            | XMLParsingSection::ProcessingInstruction(element)
+            | XMLParsingSection::Content(element) => {
+                if let (
+                    XMLElement::Content(content) | XMLElement::Text(content),
+                    Some((
+                        XMLElement::Content(previous_content) | XMLElement::Text(previous_content),
+                        previous_span,
+                    )),
+                ) = (&element, result.last_mut())
+                {
+                    // like at an end-tag, the text around an unclosed element that was dropped is one run of text: <a>1<b>2 -> 12
+                    *previous_content = Cow::Owned(format!("{previous_content}{content}"));
+                    previous_span.end = span.end;
+                    continue;
+                }
+                result.push((element, span))
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub enum Event<'a> {
+    Start(Cow<'a, str>, Attributes<'a>),
+    End(Cow<'a, str>),
+    Empty(Cow<'a, str>, Attributes<'a>), // an empty-element tag
+    Text(Cow<'a, str>),
+    Comment(Cow<'a, str>),
+    Cdata(Cow<'a, str>),
+    Doctype(Cow<'a, str>),
+    ProcessingInstruction(Cow<'a, str>, Cow<'a, str>), // a processing instruction or the XML declaration, whose target is xml
+    Error(ParseError), // a section that couldn't be parsed, e.g. an unterminated comment, only returned by to_events
+}
+
+pub struct Reader<'a> {
+    sections: XMLSections<'a>,
+    pending_tag: Option<&'a str>, // the tag of a section whose content was already returned
+    options: ParseOptions,
+    entities: Vec<(&'a str, &'a str)>, // the entities declared in the DOCTYPE
+    started: bool, // whether an event was returned, after which an XML declaration is misplaced
+    depth: usize,  // the number of start-tags that were not ended yet
+    preserved_spaces: Vec<bool>, // whether whitespace is kept in each open element, like in parse
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(raw_xml: &'a str) -> Self {
+        Reader::with_options(raw_xml, &ParseOptions::default())
+    }
+    pub fn with_options(raw_xml: &'a str, options: &ParseOptions) -> Self {
+        // the reader applies lenient, preserve_whitespace and xml:space, decode_entities, strict_entities, max_depth, max_attributes and max_attribute_len
+        // strict, case_insensitive_tags, validate_names and the normalize_* options need the matching tags or change the names, so they are left to the consumer of the events
+        Reader {
+            sections: XMLSections::new(raw_xml, options.lenient),
+            pending_tag: None,
+            options: options.clone(),
+            entities: Vec::new(),
+            started: false,
+            depth: 0,
+            preserved_spaces: Vec::new(),
+        }
+    }
+}
+
+fn parse_event<'a>(section: &'a str, options: &ParseOptions) -> Result<Event<'a>, ParseError> {
+    let section = if section.starts_with("<!--") {
+        parse_comment(section)?
+    } else if section.starts_with("<![CDATA[") {
+        parse_cdata(section)?
+    } else if section.starts_with("<!DOCTYPE") {
+        parse_doctype(section)?
+    } else if section.starts_with("<?") {
+        parse_processing_instruction(section, options)?
+    } else if section.starts_with("</") {
+        parse_element_stop_tag(section)?
+    } else if section.ends_with("/>") {
+        parse_empty_element_tag(section, options)?
+    } else {
+        parse_element_start_tag(section, options)?
+    };
+    Ok(match section {
+        XMLParsingSection::ElementStart(name, attributes) => {
+            Event::Start(Cow::Borrowed(name), attributes)
+        }
+        XMLParsingSection::ElementStop(name) => Event::End(Cow::Borrowed(name)),
+        XMLParsingSection::EmptyElement(mut element)
+        | XMLParsingSection::Comment(mut element)
+        | XMLParsingSection::Cdata(mut element)
+        | XMLParsingSection::Doctype(mut element)
+        | XMLParsingSection::ProcessingInstruction(mut element)
+        | XMLParsingSection::Declaration(mut element, _) => {
+            // the strings are taken out of the element, which can't be destructured as it implements Drop
+            use std::mem::take;
+            match &mut element {
+                XMLElement::EmptyElement(name, attributes) => {
+                    Event::Empty(take(name), take(attributes))
+                }
+                XMLElement::Comment(comment) => Event::Comment(take(comment)),
+                XMLElement::Cdata(cdata) => Event::Cdata(take(cdata)),
+                XMLElement::Doctype(doctype) => Event::Doctype(take(doctype)),
+                XMLElement::ProcessingInstruction(target, data) => {
+                    Event::ProcessingInstruction(take(target), take(data))
+                }
+                _ => unreachable!("the parse_* helpers only return their own kind of section"),
+            }
+        }
+        _ => unreachable!("the parse_* helpers only return their own kind of section"),
+    })
+}
+
+impl<'a> Iterator for Reader<'a> {
+    type Item = Result<Event<'a>, ParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // unlike parse, the reader keeps no elements around, so every section is returned as soon as it is read
+        loop {
+            let section = match self.pending_tag.take() {
+                Some(tag) => tag,
+                None => self.sections.next()?,
+            };
+            let tag_start = find_tag_start(section, self.options.lenient).unwrap_or(section.len());
+            if tag_start == 0 {
+                if is_declaration(section) && self.started {
+                    return Some(Err(ParseError::MisplacedDeclaration));
+                }
+                self.started = true;
+                if section.starts_with("<!DOCTYPE") {
+                    self.entities = parse_entity_declarations(section);
+                }
+                let event = parse_event(section, &self.options);
+                match &event {
+                    Ok(Event::Start(_, attributes)) => {
+                        self.depth += 1;
+                        self.preserved_spaces.push(preserves_space(
+                            attributes,
+                            self.preserved_spaces.last().copied(),
+                            &self.options,
+                        ));
+                        if self
+                            .options
+                            .max_depth
+                            .is_some_and(|max_depth| self.depth > max_depth)
+                        {
+                            return Some(Err(ParseError::DepthExceeded));
+                        }
+                    }
+                    Ok(Event::End(_)) => {
+                        self.depth = self.depth.saturating_sub(1);
+                        self.preserved_spaces.pop();
+                    }
+                    _ => {}
+                }
+                return Some(event);
+            }
+            let (content, tag) = section.split_at(tag_start);
+            if !tag.is_empty() {
+                self.pending_tag = Some(tag);
+            }
+            let preserve_whitespace = self.preserved_spaces.last() == Some(&true); // like in parse, whitespace outside of any element is never kept
+            if !is_omitted_content(content, preserve_whitespace) {
+                self.started = true;
+                return Some(parse_content(content, &self.options, &self.entities).map(
+                    |section| {
+                        let XMLParsingSection::Content(element) = section else {
+                            unreachable!("parse_content only returns contents");
+                        };
+                        let Some(text) = element.into_text() else {
+                            unreachable!("parse_content only returns contents");
+                        };
+                        Event::Text(text)
+                    },
+                ));
+            }
+        }
+    }
+}
+
+pub fn to_events(raw_xml: &str) -> impl Iterator<Item = Event<'_>> {
+    // like a reader, but an error is returned as an event, so a consumer can decide to continue after a bad section
+    to_events_with_options(raw_xml, &ParseOptions::default())
+}
+
+pub fn to_events_with_options<'a>(
+    raw_xml: &'a str,
+    options: &ParseOptions,
+) -> impl Iterator<Item = Event<'a>> {
+    // the options are applied like by Reader::with_options
+    Reader::with_options(raw_xml, options).map(|event| event.unwrap_or_else(Event::Error))
+}
+
+pub fn is_well_formed(raw_xml: &str) -> Result<(), ParseError> {
+    is_well_formed_with_options(raw_xml, &ParseOptions::default())
+}
+
+pub fn is_well_formed_with_options(
+    raw_xml: &str,
+    options: &ParseOptions,
+) -> Result<(), ParseError> {
+    // checks the document with a reader, so no tree is built and only the names of the open elements are kept
+    // the options are applied like by Reader::with_options, so the limits protect this check as well
+    let mut open_elements = Vec::<Cow<str>>::new();
+    for event in Reader::with_options(raw_xml, options) {
+        match event? {
+            Event::Start(name, _) => open_elements.push(name),
+            Event::End(name) => {
+                let open_element = open_elements.pop();
+                if open_element.as_deref() != Some(&*name) {
+                    // the end-tag does not close the innermost open element: <a><b></a>
+                    return Err(ParseError::UnmatchedEndTag(name.to_string()));
+                }
+            }
+            _ => {}
+        }
+    }
+    if let Some(name) = open_elements.pop() {
+        return Err(ParseError::UnclosedElement(name.to_string())); // the document ended before all elements were closed
+    }
+    Ok(())
+}
+
+pub struct Writer<W: Write> {
+    writer: W,
+    open_elements: Vec<String>, // the names of the started elements, so the end-tags can be checked
+    unclosed_tag: Option<&'static str>, // the delimiter of the tag that still accepts attributes, > for a start-tag and /> for an empty-element tag
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+impl<W: Write> Writer<W> {
+    pub fn new(writer: W) -> Self {
+        Writer {
+            writer,
+            open_elements: Vec::new(),
+            unclosed_tag: None,
+        }
+    }
+    fn close_tag(&mut self) -> io::Result<()> {
+        match self.unclosed_tag.take() {
+            Some(delimiter) => self.writer.write_all(delimiter.as_bytes()),
+            None => Ok(()),
+        }
+    }
+    pub fn write_start(&mut self, name: &str) -> io::Result<()> {
+        self.close_tag()?;
+        write!(self.writer, "<{}", name)?;
+        self.open_elements.push(name.to_string());
+        self.unclosed_tag = Some(">");
+        Ok(())
+    }
+    pub fn write_empty(&mut self, name: &str) -> io::Result<()> {
+        self.close_tag()?;
+        write!(self.writer, "<{}", name)?;
+        self.unclosed_tag = Some("/>");
+        Ok(())
+    }
+    pub fn write_attr(&mut self, name: &str, value: &str) -> io::Result<()> {
+        // adds an attribute to the last start- or empty-element tag, as long as nothing was written after it
+        if self.unclosed_tag.is_none() {
+            return Err(invalid_input(format!(
+                "attribute {} written outside of a tag",
+                name
+            )));
+        }
+        write!(self.writer, " {}=\"{}\"", name, escape_attr(value))
+    }
+    pub fn write_text(&mut self, text: &str) -> io::Result<()> {
+        self.close_tag()?;
+        self.writer.write_all(escape_text(text).as_bytes())
+    }
+    pub fn write_end(&mut self, name: &str) -> io::Result<()> {
+        // an element without any content is written as an empty-element tag: <a></a> -> <a/>
+        if self.open_elements.last().map(String::as_str) != Some(name) {
+            return Err(invalid_input(format!(
+                "end-tag </{}> does not match the open element",
+                name
+            )));
+        }
+        self.open_elements.pop();
+        if self.unclosed_tag == Some(">") {
+            self.unclosed_tag = None;
+            return self.writer.write_all(b"/>");
+        }
+        self.close_tag()?;
+        write!(self.writer, "</{}>", name)
+    }
+    pub fn finish(mut self) -> io::Result<W> {
+        // returns the sink once every element is closed
+        if let Some(name) = self.open_elements.pop() {
+            return Err(invalid_input(format!("unclosed element <{}>", name)));
+        }
+        self.close_tag()?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doctype_subset_with_comments_and_processing_instructions() {
+        let options = ParseOptions {
+            decode_entities: true,
+            ..ParseOptions::default()
+        };
+        let result = parse_with_options(
+            "<!DOCTYPE x [<!-- it's > here --><!ENTITY y \"a]>b\"><?pi x > y?>]><a>&y;</a>",
+            &options,
+        )
+        .unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[1].text(), Some("a]>b"));
+    }
+
+    #[test]
+    fn deeply_nested_doctype_does_not_overflow_the_stack() {
+        let raw_xml = "<!DOCTYPE a [".repeat(50000);
+        assert!(parse(&raw_xml).is_err());
+        assert!(is_well_formed(&raw_xml).is_err());
+        assert_eq!(Reader::new(&raw_xml).count(), 1);
+    }
+
+    #[test]
+    fn parser_reuses_its_buffers_for_documents_with_different_lifetimes() {
+        let mut parser = Parser::new();
+        for index in 0..3 {
+            let raw_xml = format!("<a n=\"{}\"><b/></a>", index);
+            let mut result = Vec::new();
+            parser.parse_into(&raw_xml, &mut result).unwrap();
+            assert_eq!(result[0].get_attr("n"), Some(index.to_string().as_str()));
+        }
+        for index in 0..3 {
+            let raw_xml = format!("<a>{}</a>", index);
+            let result = parser.parse(&raw_xml).unwrap();
+            assert_eq!(result[0].text(), Some(index.to_string().as_str()));
+            parser.recycle(result);
+        }
+        assert_eq!(
+            parser.parse("<a></b>").unwrap_err(),
+            ParseError::UnmatchedEndTag("b".to_string())
+        );
+        assert_eq!(parser.parse("<c/>").unwrap().len(), 1); // an error leaves no nodes behind
+    }
+
+    #[test]
+    fn decoded_and_built_text_is_escaped_when_written() {
+        let element = ElementBuilder::new("p").text("AT&T; &copy;").build();
+        assert_eq!(element.to_string(), "<p>AT&amp;T; &amp;copy;</p>");
+        let options = ParseOptions {
+            decode_entities: true,
+            ..ParseOptions::default()
+        };
+        let decoded = parse_with_options("<p>&amp;lt;</p>", &options).unwrap();
+        assert_eq!(decoded[0].text(), Some("&lt;"));
+        assert_eq!(decoded[0].inner_text(), "&lt;");
+        assert_eq!(decoded[0].to_string(), "<p>&amp;lt;</p>");
+        let raw = parse("<p>&amp;lt;</p>").unwrap();
+        assert_eq!(raw[0].text(), Some("&amp;lt;"));
+        assert_eq!(raw[0].to_string(), "<p>&amp;lt;</p>");
+        assert!(raw[0].structurally_eq(&decoded[0]));
+    }
+
+    #[test]
+    fn long_runs_of_ampersands_are_encoded() {
+        let text = "&".repeat(100_000);
+        assert_eq!(encode_entities(&text).len(), 5 * text.len());
+        assert_eq!(decode_entities(&text), text);
+        assert_eq!(
+            encode_entities("&amp; &#169; a & b"),
+            "&amp; &#169; a &amp; b"
+        );
+    }
+
+    #[test]
+    fn declaration_is_written_as_a_processing_instruction() {
+        let raw_xml = r#"<?xml version="1.0" encoding="UTF-8"?><a>x</a>"#;
+        let nodes = parse(raw_xml).unwrap();
+        assert!(matches!(
+            &nodes[0],
+            XMLElement::ProcessingInstruction(target, data)
+                if target == "xml" && data == r#"version="1.0" encoding="UTF-8""#
+        ));
+        let written: String = nodes.iter().map(ToString::to_string).collect();
+        assert_eq!(written, raw_xml);
+        let (declaration, nodes) = parse_document(raw_xml).unwrap();
+        assert_eq!(declaration.unwrap().encoding.as_deref(), Some("UTF-8"));
+        assert_eq!(nodes.len(), 1);
+        let mut reader = Reader::new(raw_xml);
+        assert!(matches!(
+            reader.next(),
+            Some(Ok(Event::ProcessingInstruction(target, _))) if target == "xml"
+        ));
+    }
+
+    #[test]
+    fn renaming_attributes_never_duplicates_a_name() {
+        let mut root = XMLElement::try_from(r#"<a x="1" y="2"><b x="3"/></a>"#).unwrap();
+        root.rename_attr_all("x", "y");
+        assert_eq!(root.to_string(), r#"<a x="1" y="2"><b y="3"/></a>"#);
+    }
+
+    #[test]
+    fn for_each_mut_visits_nested_matches() {
+        let mut root = XMLElement::try_from("<a><b><b/></b><c><b/></c></a>").unwrap();
+        assert_eq!(root.find_all_mut("b").len(), 2);
+        let mut visited = 0;
+        root.for_each_mut("b", |element| {
+            visited += 1;
+            element
+                .attributes_mut()
+                .unwrap()
+                .insert(Cow::Borrowed("n"), Some(Cow::Owned(visited.to_string())));
+        });
+        assert_eq!(visited, 3);
+        assert_eq!(
+            root.to_string(),
+            r#"<a><b n="1"><b n="2"/></b><c><b n="3"/></c></a>"#
+        );
+    }
+
+    fn deep_chain(depth: usize) -> String {
+        format!("{}x{}", "<a>".repeat(depth), "</a>".repeat(depth))
+    }
+
+    #[test]
+    fn find_all_mut_on_a_deep_chain_does_not_overflow_the_stack() {
+        let raw_xml = deep_chain(100_000);
+        let mut nodes = parse(&raw_xml).unwrap();
+        assert!(nodes[0].find_all_mut("zz").is_empty());
+        assert_eq!(nodes[0].find_all_mut("a").len(), 1); // the outermost match
+    }
+
+    #[test]
+    fn serializing_a_deep_chain_does_not_overflow_the_stack() {
+        let raw_xml = deep_chain(10_000);
+        let root = XMLElement::try_from(raw_xml.as_str()).unwrap();
+        assert_eq!(root.to_string(), raw_xml);
+    }
+
+    #[test]
+    fn spans_of_a_deep_chain_do_not_overflow_the_stack() {
+        let raw_xml = deep_chain(100_000);
+        let nodes = parse_with_spans(&raw_xml).unwrap();
+        let mut span = &nodes[0].1;
+        let mut depth = 1;
+        while let Some(child) = span.children.first() {
+            span = child;
+            depth += 1;
+        }
+        assert_eq!(depth, 100_001); // the innermost span is the one of the text
+        assert_eq!((span.start_line, span.start_col), (1, 300_001));
+        assert_eq!(span.source(&raw_xml), "x");
+    }
+
+    #[test]
+    fn deep_chains_are_copied_compared_walked_and_dropped_without_recursion() {
+        let raw_xml = deep_chain(100_000);
+        let nodes = parse_owned(&raw_xml).unwrap();
+        let root = &nodes[0];
+        assert!(root.structurally_eq(&root.to_owned()));
+        assert!(root.to_pretty_string("").contains("\n<a>x</a>\n</a>\n"));
+        assert!(root.find_first("b").is_none());
+        struct Counter(usize);
+        impl Visitor for Counter {
+            fn visit_element(&mut self, _: &XMLElement) {
+                self.0 += 1;
+            }
+        }
+        let mut counter = Counter(0);
+        root.accept(&mut counter);
+        assert_eq!(counter.0, 100_000);
+        let innermost = root.descendants().last().unwrap();
+        assert_eq!(root.ancestors_of(innermost).unwrap().len(), 100_000);
+        assert_eq!(root.flatten().len(), 1);
+        let mut copy = root.to_owned();
+        copy.rename_all("a", "b");
+        copy.rename_attr_all("x", "y");
+        copy.strip_comments();
+        assert!(copy.find_first("a").is_none());
+        let linked = nodes.into_iter().next().unwrap().into_linked_tree();
+        let mut node = linked.clone();
+        while let Some(child) = node.children().first().cloned() {
+            node = child;
+        }
+        assert!(matches!(node.element(), XMLElement::Content(text) if text == "x"));
+        drop(node);
+        drop(linked);
+    }
+
+    #[test]
+    fn parts_are_moved_out_of_nodes() {
+        let root = XMLElement::try_from(r#"<a x="1">t<b/></a>"#).unwrap();
+        let (name, attributes, mut children) = root.into_parts().unwrap();
+        assert_eq!(name, "a");
+        assert_eq!(attributes.get("x"), Some(&Some(Cow::Borrowed("1"))));
+        let b = children.pop().unwrap();
+        assert_eq!(children.pop().unwrap().into_text().as_deref(), Some("t"));
+        let (name, attributes, children) = b.into_parts().unwrap();
+        assert_eq!((&*name, attributes.len(), children.len()), ("b", 0, 0));
+        assert!(XMLElement::Comment(Cow::Borrowed("c"))
+            .into_parts()
+            .is_none());
+        assert!(
+            XMLElement::EmptyElement(Cow::Borrowed("e"), Attributes::new())
+                .into_text()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn to_owned_keeps_the_order_of_children() {
+        let root = XMLElement::try_from("<a><b>1</b><!--c--><d><e/>2</d>3</a>").unwrap();
+        assert_eq!(root.to_owned().to_string(), root.to_string());
+    }
+
+    #[test]
+    fn select_returns_nested_descendants_once() {
+        let root = XMLElement::try_from("<a><b><b><c>1</c></b><c>2</c></b><c>3</c></a>").unwrap();
+        let texts = |path| {
+            root.select(path)
+                .iter()
+                .map(|element| element.inner_text())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(texts("a//b//c"), ["1", "2"]);
+        assert_eq!(texts("//c"), ["1", "2", "3"]);
+        assert_eq!(texts("a/b/c"), ["2"]);
+    }
+
+    #[test]
+    fn attribute_values_may_contain_equals_signs() {
+        let root = XMLElement::try_from(r#"<a data="a=b=c" onclick="x = y" b='="'/>"#).unwrap();
+        assert_eq!(root.get_attr("data"), Some("a=b=c"));
+        assert_eq!(root.get_attr("onclick"), Some("x = y"));
+        assert_eq!(root.get_attr("b"), Some("=\""));
+    }
+
+    #[test]
+    fn misquoted_attributes_are_not_well_formed() {
+        assert_eq!(is_well_formed(r#"<a x="1" y='2'></a>"#), Ok(()));
+        assert_eq!(
+            is_well_formed(r#"<a x="1"y="2"></a>"#),
+            Err(ParseError::MalformedAttribute)
+        );
+        assert_eq!(
+            is_well_formed(r#"<a x"y"="1"></a>"#),
+            Err(ParseError::MalformedAttribute)
+        );
+    }
+
+    #[test]
+    fn attribute_limits_are_enforced_by_the_parser_and_the_reader() {
+        let options = ParseOptions {
+            max_attributes: Some(2),
+            max_attribute_len: Some(3),
+            ..Default::default()
+        };
+        let reader_error = |raw_xml| {
+            Reader::with_options(raw_xml, &options)
+                .find_map(Result::err)
+                .or_else(|| is_well_formed_with_options(raw_xml, &options).err())
+        };
+        assert!(parse_with_options(r#"<a x="1" y="abc"/>"#, &options).is_ok());
+        for raw_xml in [r#"<a x="1" y="2" z="3"/>"#, r#"<a x="abcd"></a>"#] {
+            assert_eq!(
+                parse_with_options(raw_xml, &options).err(),
+                Some(ParseError::LimitExceeded)
+            );
+            assert_eq!(reader_error(raw_xml), Some(ParseError::LimitExceeded));
+            assert!(to_events_with_options(raw_xml, &options)
+                .any(|event| matches!(event, Event::Error(ParseError::LimitExceeded))));
+        }
+        let options = ParseOptions {
+            max_attributes: Some(1),
+            ..Default::default()
+        };
+        // the tag is rejected before its second attribute is read, so the duplicate is never found
+        assert_eq!(
+            parse_with_options(r#"<a x="1" x="2"/>"#, &options).err(),
+            Some(ParseError::LimitExceeded)
+        );
+    }
+
+    #[test]
+    fn the_reader_enforces_the_maximum_depth() {
+        let options = ParseOptions {
+            max_depth: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            is_well_formed_with_options("<a><b/><b></b></a>", &options),
+            Ok(())
+        );
+        assert_eq!(
+            is_well_formed_with_options("<a><b><c></c></b></a>", &options),
+            Err(ParseError::DepthExceeded)
+        );
+    }
+
+    #[test]
+    fn strict_mode_rejects_text_before_the_root_element() {
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_with_options("oops<a/>", &options).err(),
+            Some(ParseError::TextBeforeRoot)
+        );
+        assert!(parse_with_options(" \n<!-- c -->\t<a/>", &options).is_ok());
+        assert!(parse_with_options("<a/>after", &options).is_ok());
+        assert!(parse_with_options("oops<a/>", &ParseOptions::default()).is_ok());
+    }
+
+    #[test]
+    fn text_is_available_raw_and_trimmed() {
+        let raw_xml = "<t>  hi &amp; bye  </t>";
+        let root = XMLElement::try_from(raw_xml).unwrap();
+        assert_eq!(root.text_raw(), Some("  hi &amp; bye  "));
+        assert_eq!(root.text_trimmed(), Some("hi &amp; bye"));
+        let Some(XMLElement::Content(Cow::Borrowed(content))) = root.children().first() else {
+            panic!("the content is borrowed from the document");
+        };
+        assert!(std::ptr::eq(*content, &raw_xml[3..19]));
+        let options = ParseOptions {
+            decode_entities: true,
+            ..Default::default()
+        };
+        let nodes = parse_with_options(raw_xml, &options).unwrap();
+        assert_eq!(nodes[0].text_raw(), Some("  hi & bye  "));
+    }
+
+    #[test]
+    fn malformed_documents_return_errors() {
+        assert_eq!(parse("<a").err(), Some(ParseError::UnexpectedEof));
+        assert_eq!(parse("<!-- x").err(), Some(ParseError::UnexpectedEof));
+        assert_eq!(parse("<![CDATA[x").err(), Some(ParseError::UnexpectedEof));
+        assert_eq!(
+            parse(r#"<a x="1></a>"#).err(),
+            Some(ParseError::UnterminatedAttribute)
+        );
+        assert_eq!(
+            parse("<a x=1/>").err(),
+            Some(ParseError::MalformedAttribute)
+        );
+        assert_eq!(parse("<>").err(), Some(ParseError::EmptyName));
+    }
+
+    #[test]
+    fn end_tags_without_a_start_tag_are_errors() {
+        assert_eq!(
+            parse("<a></b>").err(),
+            Some(ParseError::UnmatchedEndTag("b".to_string()))
+        );
+        assert_eq!(
+            parse("</b>").err(),
+            Some(ParseError::UnmatchedEndTag("b".to_string()))
+        );
+    }
+
+    #[test]
+    fn named_entities_are_decoded() {
+        assert_eq!(decode_entities("Tom &amp; Jerry"), "Tom & Jerry");
+        assert_eq!(
+            decode_entities("&lt;a&gt; &quot;b&quot; &apos;c&apos;"),
+            "<a> \"b\" 'c'"
+        );
+        assert_eq!(decode_entities("&nbsp; & &amp"), "&nbsp; & &amp");
+        assert!(matches!(decode_entities("plain"), Cow::Borrowed("plain")));
+        let options = ParseOptions {
+            decode_entities: true,
+            ..Default::default()
+        };
+        let nodes = parse_with_options("<a>Tom &amp; Jerry</a>", &options).unwrap();
+        assert_eq!(nodes[0].text(), Some("Tom & Jerry"));
+        assert_eq!(
+            parse("<a>Tom &amp; Jerry</a>").unwrap()[0].text(),
+            Some("Tom &amp; Jerry")
+        );
+    }
+
+    #[test]
+    fn character_references_are_decoded() {
+        assert_eq!(decode_entities("&#169; 2024 &#xA9; &#xa9;"), "© 2024 © ©");
+        let options = ParseOptions {
+            decode_entities: true,
+            ..Default::default()
+        };
+        let nodes = parse_with_options("<p>Copyright &#169; ACME</p>", &options).unwrap();
+        assert_eq!(nodes[0].text(), Some("Copyright © ACME"));
+        // malformed and out-of-range references are kept as they are written
+        assert_eq!(
+            decode_entities("&#xZZ; &#1114112; &#xD800; &#; &#XA9;"),
+            "&#xZZ; &#1114112; &#xD800; &#; &#XA9;"
+        );
+    }
+
+    #[test]
+    fn single_and_double_quoted_values_in_the_same_tag() {
+        let root = XMLElement::try_from(r#"<a x='a "b"' y="c 'd'"/>"#).unwrap();
+        assert_eq!(root.get_attr("x"), Some(r#"a "b""#));
+        assert_eq!(root.get_attr("y"), Some("c 'd'"));
+    }
+
+    #[test]
+    fn attribute_values_are_kept_whole() {
+        let root = XMLElement::try_from(r#"<book title="The Lord of the Rings" class="a  b c"/>"#)
+            .unwrap();
+        assert_eq!(root.get_attr("title"), Some("The Lord of the Rings"));
+        assert_eq!(root.attribute_tokens("class"), ["a", "b", "c"]);
+        assert!(root.attribute_tokens("missing").is_empty());
+    }
+
+    #[test]
+    fn names_and_attributes_are_separated_by_any_whitespace() {
+        let root = XMLElement::try_from("<node\n\tattr=\"x\"\r\n\tother='y'>t</node>").unwrap();
+        assert_eq!(root.name(), Some("node"));
+        assert_eq!(root.get_attr("attr"), Some("x"));
+        assert_eq!(root.get_attr("other"), Some("y"));
+        assert_eq!(XMLElement::try_from("<br\t/>").unwrap().name(), Some("br"));
+    }
+
+    #[test]
+    fn greater_than_signs_inside_attribute_values() {
+        let nodes = parse(r#"<cmp op="a > b"/><x y='>'>z</x>"#).unwrap();
+        assert_eq!(nodes[0].get_attr("op"), Some("a > b"));
+        assert_eq!(nodes[1].get_attr("y"), Some(">"));
+        assert_eq!(nodes[1].text(), Some("z"));
+    }
+
+    #[test]
+    fn accessors_work_across_variants() {
+        let root = XMLElement::try_from(r#"<a x="1">t<!--c--><![CDATA[d]]><b/></a>"#).unwrap();
+        assert_eq!(root.name(), Some("a"));
+        assert_eq!(root.get_attr("x"), Some("1"));
+        assert_eq!(root.text(), Some("t"));
+        let children = root.children();
+        assert_eq!(children.len(), 4);
+        assert_eq!(children[1].name(), None);
+        assert_eq!(children[1].text(), Some("c"));
+        assert!(children[1].attributes().is_none());
+        assert!(children[1].children().is_empty());
+        assert_eq!(children[2].text(), Some("d"));
+        assert_eq!(children[3].name(), Some("b"));
+        assert_eq!(children[3].attributes().map(Attributes::len), Some(0));
+        assert_eq!(children[3].text(), None);
+    }
+
+    #[test]
+    fn documents_round_trip_through_display() {
+        let raw_xml = r#"<a x="1" y='"'><b/>text<!-- c --><![CDATA[<d>]]></a>"#;
+        let root = XMLElement::try_from(raw_xml).unwrap();
+        let written = root.to_string();
+        assert_eq!(written, raw_xml);
+        assert!(XMLElement::try_from(written.as_str())
+            .unwrap()
+            .structurally_eq(&root));
+    }
+
+    #[test]
+    fn owned_trees_outlive_their_document() {
+        fn load() -> OwnedXMLElement {
+            let raw_xml = String::from(r#"<a x="1"><b>text</b></a>"#);
+            let root = XMLElement::try_from(raw_xml.as_str()).unwrap();
+            root.to_owned()
+        }
+        let root = load();
+        assert_eq!(root.to_string(), r#"<a x="1"><b>text</b></a>"#);
+        assert!(parse_owned("<a/>").unwrap()[0].name() == Some("a"));
+    }
+
+    #[test]
+    fn mixed_content_keeps_its_order() {
+        let root = XMLElement::try_from("<p>Hello <b>world</b>!</p>").unwrap();
+        let children = root.children();
+        assert!(matches!(&children[0], XMLElement::Content(text) if text == "Hello "));
+        assert_eq!(children[1].name(), Some("b"));
+        assert!(matches!(&children[2], XMLElement::Content(text) if text == "!"));
+        assert_eq!(children.len(), 3);
+    }
+
+    #[test]
+    fn doctypes_are_kept_at_the_top_level() {
+        let nodes = parse("<!DOCTYPE html><html/>").unwrap();
+        assert!(matches!(&nodes[0], XMLElement::Doctype(doctype) if doctype == "html"));
+        let raw_xml = r#"<!DOCTYPE x [ <!ENTITY a "b"> ]><x/>"#;
+        let nodes = parse(raw_xml).unwrap();
+        assert!(
+            matches!(&nodes[0], XMLElement::Doctype(doctype) if doctype == r#"x [ <!ENTITY a "b"> ]"#)
+        );
+        assert_eq!(nodes[1].name(), Some("x"));
+        let written: String = nodes.iter().map(ToString::to_string).collect();
+        assert_eq!(written, raw_xml);
+    }
+
+    #[test]
+    fn stylesheet_processing_instructions_are_not_declarations() {
+        let raw_xml = r#"<?xml version="1.0"?><?xml-stylesheet href="style.xsl" type="text/xsl"?><a><?php echo 1; ?></a>"#;
+        let nodes = parse(raw_xml).unwrap();
+        assert!(matches!(
+            &nodes[1],
+            XMLElement::ProcessingInstruction(target, data)
+                if target == "xml-stylesheet" && data == r#"href="style.xsl" type="text/xsl""#
+        ));
+        assert!(matches!(
+            &nodes[2].children()[0],
+            XMLElement::ProcessingInstruction(target, data) if target == "php" && data == "echo 1; "
+        ));
+        let written: String = nodes.iter().map(ToString::to_string).collect();
+        assert_eq!(written, raw_xml);
+    }
+
+    #[test]
+    fn spans_locate_elements_by_line_and_column() {
+        let raw_xml = "<a>\n  <b x=\"1\"/>\n</a>";
+        let nodes = parse_with_spans(raw_xml).unwrap();
+        let (root, span) = &nodes[0];
+        assert_eq!(root.name(), Some("a"));
+        assert_eq!(
+            (span.start_line, span.start_col, span.end_line, span.end_col),
+            (1, 1, 3, 4)
+        );
+        let child = &span.children[0];
+        assert_eq!(
+            (
+                child.start_line,
+                child.start_col,
+                child.end_line,
+                child.end_col
+            ),
+            (2, 3, 2, 12)
+        );
+        assert_eq!(child.source(raw_xml), "<b x=\"1\"/>");
+    }
+
+    #[test]
+    fn the_reader_returns_events_in_document_order() {
+        let mut events = Reader::new(r#"<a x="1">t<b/><!--c--><![CDATA[d]]></a>"#);
+        assert!(matches!(
+            events.next(),
+            Some(Ok(Event::Start(name, attributes))) if name == "a" && attributes.get("x") == Some(&Some(Cow::Borrowed("1")))
+        ));
+        assert!(matches!(events.next(), Some(Ok(Event::Text(text))) if text == "t"));
+        assert!(matches!(events.next(), Some(Ok(Event::Empty(name, _))) if name == "b"));
+        assert!(matches!(events.next(), Some(Ok(Event::Comment(text))) if text == "c"));
+        assert!(matches!(events.next(), Some(Ok(Event::Cdata(text))) if text == "d"));
+        assert!(matches!(events.next(), Some(Ok(Event::End(name))) if name == "a"));
+        assert!(events.next().is_none());
+        let large = format!("<log>{}</log>", "<entry>x</entry>".repeat(10_000));
+        assert_eq!(
+            Reader::new(&large)
+                .filter(|event| matches!(event, Ok(Event::Start(name, _)) if name == "entry"))
+                .count(),
+            10_000
+        );
+    }
+
+    #[test]
+    fn comments_need_no_surrounding_spaces() {
+        let root = XMLElement::try_from("<a><!--x--><!-- x --><!----></a>").unwrap();
+        let comments: Vec<_> = root
+            .children()
+            .iter()
+            .filter_map(XMLElement::text)
+            .collect();
+        assert_eq!(comments, ["x", " x ", ""]);
+        assert_eq!(root.to_string(), "<a><!--x--><!-- x --><!----></a>");
+    }
+
+    #[test]
+    fn comments_are_read_as_a_unit() {
+        let root = XMLElement::try_from("<a><!-- 1 > 0 --><!--\n<b>\n--></a>").unwrap();
+        assert!(matches!(&root.children()[0], XMLElement::Comment(text) if text == " 1 > 0 "));
+        assert!(matches!(&root.children()[1], XMLElement::Comment(text) if text == "\n<b>\n"));
+        assert_eq!(root.children().len(), 2);
+    }
+
+    #[test]
+    fn cdata_is_kept_byte_for_byte() {
+        let root =
+            XMLElement::try_from("<a><![CDATA[<b>a > b && ]] c</b>]]><![CDATA[]]></a>").unwrap();
+        assert!(
+            matches!(&root.children()[0], XMLElement::Cdata(text) if text == "<b>a > b && ]] c</b>")
+        );
+        assert!(matches!(&root.children()[1], XMLElement::Cdata(text) if text.is_empty()));
+        assert_eq!(root.inner_text(), "<b>a > b && ]] c</b>");
+    }
+
+    #[test]
+    fn valueless_and_empty_attributes() {
+        let root = XMLElement::try_from(r#"<input disabled value=""/>"#).unwrap();
+        let attributes = root.attributes().unwrap();
+        assert_eq!(attributes.get("disabled"), Some(&None));
+        assert_eq!(attributes.get("value"), Some(&Some(Cow::Borrowed(""))));
+        assert_eq!(root.get_attr("disabled"), Some(""));
+        assert_eq!(root.to_string(), r#"<input disabled value=""/>"#);
+    }
+
+    #[test]
+    fn whitespace_only_contents_can_be_preserved() {
+        let raw_xml = "<code>\n    <line/>\n</code>";
+        assert_eq!(XMLElement::try_from(raw_xml).unwrap().children().len(), 1);
+        let options = ParseOptions {
+            preserve_whitespace: true,
+            ..Default::default()
+        };
+        let nodes = parse_with_options(raw_xml, &options).unwrap();
+        let children = nodes[0].children();
+        assert_eq!(children.len(), 3);
+        assert!(matches!(&children[0], XMLElement::Content(text) if text == "\n    "));
+        assert_eq!(nodes[0].to_string(), raw_xml);
+    }
+
+    #[test]
+    fn find_all_returns_matches_at_any_depth() {
+        let root = XMLElement::try_from(
+            r#"<catalog><item id="1"/><shelf><item id="2"><item id="3"/></item></shelf></catalog>"#,
+        )
+        .unwrap();
+        let ids: Vec<_> = root
+            .find_all("item")
+            .iter()
+            .filter_map(|item| item.get_attr("id"))
+            .collect();
+        assert_eq!(ids, ["1", "2", "3"]);
+        assert_eq!(
+            root.find_first("item").and_then(|item| item.get_attr("id")),
+            Some("1")
+        );
+        assert!(root.find_first("missing").is_none());
+    }
+
+    #[test]
+    fn select_supports_child_and_descendant_steps() {
+        let root = XMLElement::try_from(
+            "<catalog><book><title>A</title></book><shelf><book><title>B</title></book></shelf></catalog>",
+        )
+        .unwrap();
+        let titles = |path| {
+            root.select(path)
+                .iter()
+                .map(|element| element.inner_text())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(titles("catalog/book/title"), ["A"]);
+        assert_eq!(titles("//title"), ["A", "B"]);
+        assert_eq!(titles("catalog//title"), ["A", "B"]);
+        assert_eq!(titles("catalog/*/book/title"), ["B"]);
+        assert!(titles("book/title").is_empty());
+    }
+
+    #[test]
+    fn descendants_are_visited_depth_first_in_document_order() {
+        let root = XMLElement::try_from("<a><b><!--c--></b><![CDATA[d]]><e/></a>").unwrap();
+        let visited: Vec<_> = root
+            .descendants()
+            .map(|node| match node {
+                XMLElement::Comment(_) => "comment",
+                XMLElement::Cdata(_) => "cdata",
+                node => node.name().unwrap(),
+            })
+            .collect();
+        assert_eq!(visited, ["b", "comment", "cdata", "e"]);
+        assert_eq!(
+            root.descendants()
+                .filter(|node| node.name().is_some())
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn inner_text_includes_nested_text() {
+        let root = XMLElement::try_from(
+            "<desc>Hello <b>bold &amp; <i>big</i></b> world<![CDATA[ &amp;]]></desc>",
+        )
+        .unwrap();
+        assert_eq!(root.inner_text(), "Hello bold & big world &amp;");
+        let options = ParseOptions {
+            decode_entities: true,
+            ..Default::default()
+        };
+        // texts that were decoded while parsing are not decoded a second time
+        let nodes = parse_with_options("<a>&amp;lt;</a>", &options).unwrap();
+        assert_eq!(nodes[0].inner_text(), "&lt;");
+    }
+
+    #[test]
+    fn duplicate_attributes_are_errors() {
+        assert_eq!(
+            parse(r#"<a x="1" x="2"/>"#).err(),
+            Some(ParseError::DuplicateAttribute("x".to_string()))
+        );
+        assert_eq!(
+            parse(r#"<a x="1" y="2" x></a>"#).err(),
+            Some(ParseError::DuplicateAttribute("x".to_string()))
+        );
+    }
+
+    #[test]
+    fn attributes_keep_their_source_order() {
+        let raw_xml = r#"<a zeta="1" alpha="2" mid="3" b="4"/>"#;
+        let root = XMLElement::try_from(raw_xml).unwrap();
+        let names: Vec<_> = root
+            .attributes()
+            .unwrap()
+            .iter()
+            .map(|(name, _)| &**name)
+            .collect();
+        assert_eq!(names, ["zeta", "alpha", "mid", "b"]);
+        assert_eq!(root.get_attr("mid"), Some("3"));
+        for _ in 0..3 {
+            assert_eq!(XMLElement::try_from(raw_xml).unwrap().to_string(), raw_xml);
+        }
+    }
+
+    #[test]
+    fn prefixes_resolve_through_nested_declarations() {
+        let root = XMLElement::try_from(
+            r#"<s:Envelope xmlns:s="urn:soap"><s:Body xmlns:m="urn:a"><m:Get xmlns:m="urn:b"/><m:Put/></s:Body></s:Envelope>"#,
+        )
+        .unwrap();
+        assert_eq!(root.prefix(), Some("s"));
+        assert_eq!(root.local_name(), Some("Envelope"));
+        let body = &root.children()[0];
+        let (get, put) = (&body.children()[0], &body.children()[1]);
+        assert_eq!(get.local_name(), Some("Get"));
+        assert_eq!(get.namespace_uri(&[&root, body]), Some("urn:b"));
+        assert_eq!(put.namespace_uri(&[&root, body]), Some("urn:a"));
+        assert_eq!(body.resolve_prefix("s", &[&root]), Some("urn:soap"));
+        assert_eq!(put.resolve_prefix("x", &[&root, body]), None);
+        let ancestors = root.ancestors_of(put).unwrap();
+        assert_eq!(ancestors.len(), 2);
+        assert!(std::ptr::eq(ancestors[0], &root) && std::ptr::eq(ancestors[1], body));
+    }
+
+    #[test]
+    fn default_namespaces_are_inherited_redeclared_and_cleared() {
+        let root = XMLElement::try_from(
+            r#"<feed xmlns="urn:atom"><entry><x xmlns="urn:other"><y/></x><z xmlns=""/></entry></feed>"#,
+        )
+        .unwrap();
+        let entry = &root.children()[0];
+        let (x, z) = (&entry.children()[0], &entry.children()[1]);
+        let y = &x.children()[0];
+        assert_eq!(root.namespace_uri(&[]), Some("urn:atom"));
+        assert_eq!(entry.namespace_uri(&[&root]), Some("urn:atom"));
+        assert_eq!(y.namespace_uri(&[&root, entry, x]), Some("urn:other"));
+        assert_eq!(z.namespace_uri(&[&root, entry]), None);
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    #[test]
+    fn elements_serialize_with_tagged_variants() {
+        let root = XMLElement::try_from(r#"<a x="1" y>t<!--c--><b/></a>"#).unwrap();
+        assert_eq!(
+            serde_json::to_value(&root).unwrap(),
+            serde_json::json!({
+                "type": "element",
+                "name": "a",
+                "attributes": {"x": "1", "y": null},
+                "children": [
+                    {"type": "content", "text": "t"},
+                    {"type": "comment", "text": "c"},
+                    {"type": "empty_element", "name": "b", "attributes": {}}
+                ]
+            })
+        );
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn deep_chains_convert_to_json_without_recursion() {
+        let raw_xml = deep_chain(100_000);
+        let nodes = parse(&raw_xml).unwrap();
+        let mut json = nodes[0].to_json();
+        let mut depth = 0;
+        // the value is taken apart from the top, as serde_json drops nested values recursively
+        while let Some(body) = json.get_mut("a") {
+            json = body.take();
+            depth += 1;
+        }
+        assert_eq!(depth, 100_000);
+        assert_eq!(json, serde_json::json!({"#text": "x"}));
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn json_collapses_repeated_children_into_arrays() {
+        let root = XMLElement::try_from(
+            r#"<library name="city"><book id="1">A &amp; B</book><book id="2"/><note>n</note></library>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            root.to_json(),
+            serde_json::json!({
+                "library": {
+                    "@name": "city",
+                    "book": [{"@id": "1", "#text": "A & B"}, {"@id": "2"}],
+                    "note": {"#text": "n"}
+                }
+            })
+        );
+    }
+
+    #[test]
+    fn a_leading_byte_order_mark_is_skipped() {
+        let nodes = parse("\u{FEFF}<?xml version=\"1.0\"?><a/>").unwrap();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[1].name(), Some("a"));
+        let (declaration, nodes) =
+            parse_document("\u{FEFF} \n<?xml version=\"1.0\"?><a/>").unwrap();
+        assert_eq!(declaration.unwrap().version, "1.0");
+        assert_eq!(nodes[0].name(), Some("a"));
+        assert_eq!(parse("\u{FEFF}<a/>").unwrap()[0].name(), Some("a"));
+    }
+
+    #[test]
+    fn bytes_are_decoded_by_their_encoding() {
+        let latin1 = b"<?xml version=\"1.0\" encoding=\"ISO-8859-1\"?><a>caf\xe9</a>";
+        assert_eq!(parse_bytes(latin1).unwrap()[1].text(), Some("café"));
+        let utf8 = "\u{FEFF}<a>café</a>".as_bytes();
+        assert_eq!(parse_bytes(utf8).unwrap()[0].text(), Some("café"));
+        let text = "<a>café</a>";
+        let mut utf16le = vec![0xFF, 0xFE];
+        utf16le.extend(text.encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(parse_bytes(&utf16le).unwrap()[0].text(), Some("café"));
+        let mut utf16be = vec![0xFE, 0xFF];
+        utf16be.extend(text.encode_utf16().flat_map(u16::to_be_bytes));
+        assert_eq!(parse_bytes(&utf16be).unwrap()[0].text(), Some("café"));
+        assert_eq!(
+            parse_bytes(b"<?xml version=\"1.0\" encoding=\"EBCDIC\"?><a/>").err(),
+            Some(ParseError::UnsupportedEncoding("EBCDIC".to_string()))
+        );
+    }
+
+    #[test]
+    fn self_closing_tags_may_have_a_space_before_the_slash() {
+        let nodes = parse(r#"<br /><img src="x" /><hr	/>"#).unwrap();
+        assert!(
+            matches!(&nodes[0], XMLElement::EmptyElement(name, attributes) if name == "br" && attributes.is_empty())
+        );
+        assert_eq!(nodes[1].name(), Some("img"));
+        assert_eq!(nodes[1].get_attr("src"), Some("x"));
+        assert_eq!(nodes[2].name(), Some("hr"));
+    }
+
+    #[test]
+    fn lenient_mode_keeps_a_literal_less_than_sign() {
+        let options = ParseOptions {
+            lenient: true,
+            ..Default::default()
+        };
+        let nodes = parse_with_options("<p>1 < 2</p>", &options).unwrap();
+        assert_eq!(nodes[0].text(), Some("1 < 2"));
+        assert_eq!(nodes[0].to_string(), "<p>1 &lt; 2</p>");
+        assert!(parse("<p>1 < 2</p>").is_err());
+    }
+
+    #[test]
+    fn builders_construct_serializable_trees() {
+        let document = ElementBuilder::new("order")
+            .attr("id", "7")
+            .child(ElementBuilder::new("item").text("AT&T").build())
+            .child(ElementBuilder::new("empty").build())
+            .build();
+        assert_eq!(
+            document.to_string(),
+            r#"<order id="7"><item>AT&amp;T</item><empty/></order>"#
+        );
+    }
+
+    #[test]
+    fn built_attributes_keep_their_value_through_a_round_trip() {
+        let element = ElementBuilder::new("a")
+            .attr("q", "&lt;")
+            .attr("r", r#"AT&T "x" <y>"#)
+            .build();
+        assert_eq!(
+            element.to_string(),
+            r#"<a q="&amp;lt;" r="AT&amp;T &quot;x&quot; &lt;y&gt;"/>"#
+        );
+        assert_eq!(element.get_attr_decoded("q").as_deref(), Some("&lt;"));
+        let written = element.to_string();
+        let parsed = XMLElement::try_from(written.as_str()).unwrap();
+        assert_eq!(parsed.get_attr_decoded("q").as_deref(), Some("&lt;"));
+        assert_eq!(
+            parsed.get_attr_decoded("r").as_deref(),
+            Some(r#"AT&T "x" <y>"#)
+        );
+    }
+
+    #[test]
+    fn well_formedness_of_balanced_and_unbalanced_documents() {
+        assert_eq!(
+            is_well_formed(r#"<?xml version="1.0"?><a x="1"><b/><!-- c --><![CDATA[<d>]]>t</a>"#),
+            Ok(())
+        );
+        assert_eq!(
+            is_well_formed("<a><b></a>"),
+            Err(ParseError::UnmatchedEndTag("a".to_string()))
+        );
+        assert_eq!(
+            is_well_formed("<a><b></b>"),
+            Err(ParseError::UnclosedElement("a".to_string()))
+        );
+        assert_eq!(
+            is_well_formed("<a><!-- c </a>"),
+            Err(ParseError::UnterminatedTag)
+        );
+        assert_eq!(
+            is_well_formed("<a><![CDATA[x"),
+            Err(ParseError::UnexpectedEof)
+        );
+        assert_eq!(
+            is_well_formed(r#"<a x="1></a>"#),
+            Err(ParseError::UnterminatedAttribute)
+        );
+    }
+
+    #[test]
+    fn case_insensitive_tags_close_elements_of_another_case() {
+        let raw_xml = "<DIV><p>x</P></div>";
+        assert!(parse(raw_xml).is_err());
+        let options = ParseOptions {
+            case_insensitive_tags: true,
+            ..Default::default()
+        };
+        let nodes = parse_with_options(raw_xml, &options).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(nodes[0].name(), Some("DIV"));
+        assert_eq!(nodes[0].children()[0].text(), Some("x"));
+    }
+
+    #[test]
+    fn documents_nested_beyond_the_maximum_depth_are_rejected() {
+        let options = ParseOptions {
+            max_depth: Some(3),
+            ..Default::default()
+        };
+        assert!(parse_with_options("<a><b><c/></b></a>", &options).is_ok());
+        assert!(parse_with_options("<a><b><c></c></b></a>", &options).is_ok());
+        assert_eq!(
+            parse_with_options("<a><b><c><d></d></c></b></a>", &options).err(),
+            Some(ParseError::DepthExceeded)
+        );
+    }
+
+    #[test]
+    fn the_declaration_must_come_first() {
+        let declaration = r#"<?xml version="1.0"?>"#;
+        assert!(parse(&format!(" \n{declaration}<a/>")).is_ok());
+        for raw_xml in [
+            format!("<a/>{declaration}"),
+            format!("<a>{declaration}</a>"),
+            format!("<!-- c -->{declaration}<a/>"),
+            format!("{declaration}{declaration}<a/>"),
+        ] {
+            assert_eq!(
+                parse(&raw_xml).err(),
+                Some(ParseError::MisplacedDeclaration)
+            );
+            assert_eq!(
+                is_well_formed(&raw_xml),
+                Err(ParseError::MisplacedDeclaration)
+            );
+        }
+        assert!(parse("<a><?target data?></a><?other?>").is_ok());
+    }
+
+    #[test]
+    fn get_attr_returns_the_whole_value() {
+        let root =
+            XMLElement::try_from(r#"<a href="https://example.com/a b" title="x  y"/>"#).unwrap();
+        assert_eq!(root.get_attr("href"), Some("https://example.com/a b"));
+        assert_eq!(root.get_attr("title"), Some("x  y"));
+        assert_eq!(root.get_attr("missing"), None);
+        assert_eq!(
+            XMLElement::Comment(Cow::Borrowed("c")).get_attr("href"),
+            None
+        );
+    }
+
+    #[test]
+    fn strict_mode_rejects_unclosed_elements() {
+        let options = ParseOptions {
+            strict: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_with_options("<a><b></a>", &options).err(),
+            Some(ParseError::UnclosedElement("b".to_string()))
+        );
+        assert_eq!(
+            parse_with_options("<a>", &options).err(),
+            Some(ParseError::UnclosedElement("a".to_string()))
+        );
+        assert!(parse("<a><b></a>").is_ok());
+        assert!(parse("<a>").is_ok());
+    }
+
+    #[test]
+    fn parse_owned_results_outlive_the_input() {
+        fn load() -> Vec<OwnedXMLElement> {
+            let raw_xml = String::from(r#"<a x="1">t</a><!-- c -->"#);
+            let nodes = parse_owned(&raw_xml).unwrap();
+            drop(raw_xml);
+            nodes
+        }
+        let nodes = load();
+        assert_eq!(nodes.len(), 2);
+        assert_eq!(nodes[0].to_string(), r#"<a x="1">t</a>"#);
+        assert!(parse_owned("<a").is_err());
+    }
+
+    #[test]
+    fn top_level_nodes_of_fragments_keep_their_order() {
+        let nodes = parse("<a/><!-- 1 --><b>x</b><c/><!-- 2 --><d><e/></d>").unwrap();
+        let kinds: Vec<_> = nodes
+            .iter()
+            .map(|node| node.name().or(node.text()).unwrap())
+            .collect();
+        assert_eq!(kinds, ["a", " 1 ", "b", "c", " 2 ", "d"]);
+    }
+
+    #[test]
+    fn sections_are_read_lazily() {
+        let raw_xml = format!(r#"<a x="1"  y="2">t</a>{}"#, "<b/>".repeat(100_000));
+        let mut sections = XMLSections::new(&raw_xml, false);
+        assert_eq!(sections.next(), Some(r#"<a x="1"  y="2">"#));
+        assert_eq!(sections.next(), Some("t</a>"));
+        assert_eq!(sections.count(), 100_000);
+    }
+
+    #[test]
+    fn declarations_are_parsed_into_their_fields() {
+        let declaration =
+            parse_declaration(r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><a/>"#)
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            declaration,
+            Declaration {
+                version: Cow::Borrowed("1.0"),
+                encoding: Some(Cow::Borrowed("UTF-8")),
+                standalone: Some(true),
+            }
+        );
+        let declaration = parse_declaration(r#"<?xml version='1.1'?><a/>"#)
+            .unwrap()
+            .unwrap();
+        assert_eq!((declaration.encoding, declaration.standalone), (None, None));
+        assert_eq!(parse_declaration("<a/>"), Ok(None));
+        for raw_xml in [
+            "<?xml?><a/>",
+            r#"<?xml version="2.0"?>"#,
+            r#"<?xml version="1.0" standalone="maybe"?>"#,
+            r#"<?xml version="1.0">"#,
+        ] {
+            assert_eq!(
+                parse_declaration(raw_xml),
+                Err(ParseError::MalformedDeclaration)
+            );
+        }
+        let (declaration, nodes) =
+            parse_document(r#"<?xml version="1.0" standalone="no"?><a/>"#).unwrap();
+        assert_eq!(declaration.unwrap().standalone, Some(false));
+        assert_eq!(nodes.len(), 1);
+    }
+
+    #[test]
+    fn children_named_returns_only_direct_children() {
+        let root =
+            XMLElement::try_from("<table><row>1</row><group><row>2</row></group><row/></table>")
+                .unwrap();
+        let rows: Vec<_> = root.children_named("row").collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].text(), Some("1"));
+        assert!(matches!(rows[1], XMLElement::EmptyElement(_, _)));
+        assert_eq!(root.find_all("row").len(), 3);
+    }
+
+    #[test]
+    fn comments_cdata_and_processing_instructions_keep_their_position() {
+        let raw_xml = "<a><!--c--><b/>text<![CDATA[d]]><?pi x?>tail</a>";
+        let root = XMLElement::try_from(raw_xml).unwrap();
+        let children = root.children();
+        assert!(matches!(&children[0], XMLElement::Comment(text) if text == "c"));
+        assert_eq!(children[1].name(), Some("b"));
+        assert!(matches!(&children[2], XMLElement::Content(text) if text == "text"));
+        assert!(matches!(&children[3], XMLElement::Cdata(text) if text == "d"));
+        assert!(
+            matches!(&children[4], XMLElement::ProcessingInstruction(target, _) if target == "pi")
+        );
+        assert!(matches!(&children[5], XMLElement::Content(text) if text == "tail"));
+        assert_eq!(root.to_string(), raw_xml);
+    }
+
+    #[test]
+    fn pretty_strings_indent_every_level() {
+        let root = XMLElement::try_from(
+            r#"<config><server port="80"><name>a</name><tls/></server><p>x <b>y</b></p><c><![CDATA[ <raw> ]]></c></config>"#,
+        )
+        .unwrap();
+        assert_eq!(
+            root.to_pretty_string("  "),
+            "<config>\n  <server port=\"80\">\n    <name>a</name>\n    <tls/>\n  </server>\n  <p>x <b>y</b></p>\n  <c><![CDATA[ <raw> ]]></c>\n</config>"
+        );
+        assert_eq!(
+            XMLElement::try_from("<a/>").unwrap().to_pretty_string("\t"),
+            "<a/>"
+        );
+    }
+
+    #[test]
+    fn attribute_values_can_be_decoded_on_access() {
+        let root = XMLElement::try_from(r#"<a title="Tom &amp; Jerry &lt;3" n="&#65;"/>"#).unwrap();
+        assert_eq!(root.get_attr("title"), Some("Tom &amp; Jerry &lt;3"));
+        assert_eq!(
+            root.get_attr_decoded("title").as_deref(),
+            Some("Tom & Jerry <3")
+        );
+        assert_eq!(root.get_attr_decoded("n").as_deref(), Some("A"));
+        assert_eq!(root.get_attr_decoded("missing"), None);
+    }
+
+    #[test]
+    fn unterminated_attribute_values_are_reported() {
+        assert_eq!(
+            parse(r#"<a title="oops>"#).err(),
+            Some(ParseError::UnterminatedAttribute)
+        );
+        assert_eq!(
+            parse(r#"<a title="oops></a><b/>"#).err(),
+            Some(ParseError::UnterminatedAttribute)
+        );
+        assert_eq!(
+            parse("<a title='oops>").err(),
+            Some(ParseError::UnterminatedAttribute)
+        );
+        assert_eq!(
+            parse(r#"<a title="oops>text"#).err(),
+            Some(ParseError::UnterminatedAttribute)
+        );
+        assert_eq!(
+            parse(r#"<r><a title="oops/>te>xt"#).err(),
+            Some(ParseError::UnterminatedAttribute)
+        );
+        assert!(Reader::new(r#"<a title="oops>text"#)
+            .any(|event| matches!(event, Err(ParseError::UnterminatedAttribute))));
+        assert_eq!(
+            parse(r#"<a title="done""#).err(),
+            Some(ParseError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn namespaced_attributes_are_found_by_their_uri() {
+        let root = XMLElement::try_from(
+            r##"<svg xmlns:xlink="http://www.w3.org/1999/xlink"><use xlink:href="#a"/><g xmlns:l="http://www.w3.org/1999/xlink"><use l:href="#b" href="#plain"/></g></svg>"##,
+        )
+        .unwrap();
+        let xlink = "http://www.w3.org/1999/xlink";
+        let first = &root.children()[0];
+        let group = &root.children()[1];
+        let second = &group.children()[0];
+        assert_eq!(first.get_attr_ns(xlink, "href", &[&root]), Some("#a"));
+        assert_eq!(
+            second.get_attr_ns(xlink, "href", &[&root, group]),
+            Some("#b")
+        );
+        assert_eq!(
+            second.get_attr_ns("urn:other", "href", &[&root, group]),
+            None
+        );
+    }
+
+    #[test]
+    fn count_matching_counts_empty_elements() {
+        let root = XMLElement::try_from("<a><b/><c><b/><d/></c><!-- <b/> -->t</a>").unwrap();
+        assert_eq!(
+            root.count_matching(|element| matches!(element, XMLElement::EmptyElement(_, _))),
+            3
+        );
+        assert_eq!(
+            root.count_matching(|element| element.name() == Some("b")),
+            2
+        );
+        assert_eq!(root.count_matching(|element| element.name().is_some()), 5);
+    }
+
+    #[test]
+    fn degenerate_documents() {
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("   \n\t").unwrap().is_empty());
+        let nodes = parse("<?xml?>").unwrap();
+        assert!(
+            matches!(&nodes[..], [XMLElement::ProcessingInstruction(target, _)] if target == "xml")
+        );
+        assert_eq!(parse("<").err(), Some(ParseError::UnexpectedEof));
+        assert_eq!(parse("</>").err(), Some(ParseError::EmptyName));
+        assert_eq!(parse("<>").err(), Some(ParseError::EmptyName));
+        assert_eq!(
+            XMLElement::try_from("   ").err(),
+            Some(ParseError::NoRootElement)
+        );
+    }
+
+    #[test]
+    fn spans_return_the_source_of_an_element() {
+        let raw_xml =
+            "<doc>\n  <keep a='1'  b=\"2\" >verbatim &amp; <x/></keep>\n  <other/>\n</doc>";
+        let nodes = parse_with_spans(raw_xml).unwrap();
+        let (root, span) = &nodes[0];
+        assert_eq!(span.source(raw_xml), raw_xml);
+        let keep = root
+            .children()
+            .iter()
+            .position(|child| child.name() == Some("keep"))
+            .unwrap();
+        assert_eq!(
+            span.children[keep].source(raw_xml),
+            "<keep a='1'  b=\"2\" >verbatim &amp; <x/></keep>"
+        );
+        assert_eq!(span.children[keep].children[1].source(raw_xml), "<x/>");
+    }
+
+    #[test]
+    fn attributes_are_separated_by_runs_of_whitespace() {
+        let root = XMLElement::try_from("<a x=\"1\"  y=\"2\"\t\tz='3' \n\t w=\"4\"/>").unwrap();
+        let values: Vec<_> = ["x", "y", "z", "w"]
+            .iter()
+            .filter_map(|name| root.get_attr(name))
+            .collect();
+        assert_eq!(values, ["1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn statistics_of_a_known_sample() {
+        let raw_xml = r#"<a x="1" y="2"><b z="3">t</b><c/>u<!--c--><d><e/></d></a>"#;
+        let (nodes, stats) = parse_with_stats(raw_xml).unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(
+            stats,
+            ParseStats {
+                bytes: raw_xml.len(),
+                elements: 5,
+                attributes: 3,
+                text_nodes: 2,
+                max_depth: 3,
+            }
+        );
+    }
+
+    #[test]
+    fn statistics_count_the_returned_nodes() {
+        let (nodes, stats) = parse_with_stats("<a>1<b>2</a>").unwrap();
+        assert_eq!(nodes[0].to_string(), "<a>12</a>");
+        assert_eq!(
+            (stats.elements, stats.text_nodes, stats.max_depth),
+            (1, 1, 1)
+        );
+        let (_, stats) = parse_with_stats("<a>1<b x='y'>2").unwrap();
+        assert_eq!(
+            (stats.elements, stats.attributes, stats.text_nodes),
+            (0, 0, 1)
+        );
+    }
+
+    #[test]
+    fn cdata_is_never_decoded() {
+        let options = ParseOptions {
+            decode_entities: true,
+            ..Default::default()
+        };
+        let nodes = parse_with_options(
+            "<a>&amp; is decoded<![CDATA[&amp; is literal]]></a>",
+            &options,
+        )
+        .unwrap();
+        let children = nodes[0].children();
+        assert!(matches!(&children[0], XMLElement::Text(text) if text == "& is decoded"));
+        assert!(matches!(&children[1], XMLElement::Cdata(text) if text == "&amp; is literal"));
+        assert_eq!(nodes[0].inner_text(), "& is decoded&amp; is literal");
+    }
+
+    #[test]
+    fn structural_equality_ignores_attribute_order_and_whitespace() {
+        let tree = |raw_xml| XMLElement::try_from(raw_xml).unwrap();
+        assert!(tree(r#"<a x="1" y="2"> <b/> </a>"#)
+            .structurally_eq(&tree(r#"<a y="2" x="1"><b></b></a>"#)));
+        assert!(tree("<a>\n  <b>t</b>\n</a>").structurally_eq(&tree("<a><b>t</b></a>")));
+        assert!(!tree("<a><b>t</b></a>").structurally_eq(&tree("<a><b>t </b></a>")));
+        assert!(!tree(r#"<a x="1"/>"#).structurally_eq(&tree(r#"<a x="2"/>"#)));
+        assert!(!tree(r#"<a x="1"/>"#).structurally_eq(&tree(r#"<a x="1" y="2"/>"#)));
+        assert!(!tree("<a><b/><c/></a>").structurally_eq(&tree("<a><c/><b/></a>")));
+    }
+
+    #[test]
+    fn structural_equality_compares_decoded_text() {
+        let tree = |raw_xml| XMLElement::try_from(raw_xml).unwrap();
+        assert!(tree("<a>&amp;</a>").structurally_eq(&tree("<a>&#38;</a>")));
+        let options = ParseOptions {
+            decode_entities: true,
+            ..Default::default()
+        };
+        let decoded = parse_with_options("<a>&#38;</a>", &options).unwrap();
+        assert!(tree("<a>&amp;</a>").structurally_eq(&decoded[0]));
+        assert!(decoded[0].structurally_eq(&tree("<a>&amp;</a>")));
+        assert!(!tree("<a>&amp;</a>").structurally_eq(&tree("<a>&amp;amp;</a>")));
+        assert!(!tree("<a>&lt;</a>").structurally_eq(&tree("<a><![CDATA[<]]></a>")));
+    }
+
+    #[test]
+    fn entities_declared_in_the_doctype_are_expanded() {
+        let options = ParseOptions {
+            decode_entities: true,
+            ..Default::default()
+        };
+        let raw_xml = r#"<!DOCTYPE a [ <!ENTITY foo "bar"> <!ENTITY co 'ACME &amp; Co'> ]><a>&foo; by &co; &amp; &unknown;</a>"#;
+        let nodes = parse_with_options(raw_xml, &options).unwrap();
+        assert_eq!(nodes[1].text(), Some("bar by ACME & Co & &unknown;"));
+        assert_eq!(decode_entities_with("&foo;&lt;", &[("foo", "bar")]), "bar<");
+    }
+
+    #[test]
+    fn find_all_mut_edits_the_matched_elements() {
+        let mut root =
+            XMLElement::try_from(r#"<a><link rel="next"/><p><link rel="prev"/></p></a>"#).unwrap();
+        for link in root.find_all_mut("link") {
+            let attributes = link.attributes_mut().unwrap();
+            let value = attributes.get("rel").cloned().flatten().unwrap();
+            attributes.insert(Cow::Borrowed("rel"), Some(Cow::Owned(value.to_uppercase())));
+        }
+        assert_eq!(
+            root.to_string(),
+            r#"<a><link rel="NEXT"/><p><link rel="PREV"/></p></a>"#
+        );
+    }
+
+    #[test]
+    fn a_declaration_without_its_question_mark_is_malformed() {
+        let raw_xml = r#"<?xml version="1.0"><a/>"#;
+        assert_eq!(parse(raw_xml).err(), Some(ParseError::MalformedDeclaration));
+        assert_eq!(
+            parse_declaration(raw_xml),
+            Err(ParseError::MalformedDeclaration)
+        );
+        assert_eq!(
+            parse(r#"<?xml version="1.0"?"#).err(),
+            Some(ParseError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn comments_are_stripped_at_every_level() {
+        let root = XMLElement::try_from("<a><!--1-->x<b><!--2--><c/><!--3--></b>y</a>")
+            .unwrap()
+            .without_comments();
+        assert_eq!(
+            root.count_matching(|node| matches!(node, XMLElement::Comment(_))),
+            0
+        );
+        assert!(!root
+            .descendants()
+            .any(|node| matches!(node, XMLElement::Comment(_))));
+        assert_eq!(root.to_string(), "<a>x<b><c/></b>y</a>");
+    }
+
+    #[test]
+    fn crlf_and_tab_indentation_is_not_content() {
+        let nodes = parse("<a>\r\n\t<b>x\r\ny</b>\r\n\t<c/>\r\n</a>\r\n").unwrap();
+        assert_eq!(nodes.len(), 1);
+        let children = nodes[0].children();
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].text(), Some("x\r\ny"));
+    }
+
+    #[test]
+    fn text_around_a_dropped_element_is_merged() {
+        let nodes = parse("<a>1<b>2</a>").unwrap();
+        let children = nodes[0].children();
+        assert_eq!(children.len(), 1);
+        assert!(matches!(&children[0], XMLElement::Content(text) if text == "12"));
+        let nodes = parse("<a>1<b/>2</a>").unwrap();
+        assert_eq!(nodes[0].children().len(), 3); // an element that is kept separates the texts
+        let nodes = parse("<a>1<b>2").unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert!(matches!(&nodes[0], XMLElement::Content(text) if text == "12"));
+        let nodes = parse("<a>1<!--c--><b>2").unwrap();
+        assert_eq!(nodes.len(), 3);
+        let (_, span) = &parse_with_spans("<a>1<b>2").unwrap()[0];
+        assert_eq!((span.start, span.end), (3, 8));
+    }
+
+    #[test]
+    fn documents_are_read_from_a_reader() {
+        let cursor = io::Cursor::new(b"<?xml version=\"1.0\"?><a x=\"1\">t</a>".to_vec());
+        let nodes = parse_reader(cursor).unwrap();
+        assert_eq!(nodes[1].to_string(), r#"<a x="1">t</a>"#);
+        struct Failing;
+        impl Read for Failing {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                Err(io::Error::other("disconnected"))
+            }
+        }
+        assert_eq!(
+            parse_reader(Failing).err(),
+            Some(ParseError::Io("disconnected".to_string()))
+        );
+    }
+
+    #[test]
+    fn names_are_validated_when_requested() {
+        let options = ParseOptions {
+            validate_names: true,
+            ..Default::default()
+        };
+        for raw_xml in ["<_a-b.c:d\u{e9}/>", r#"<a x:y="1" z2="2"></a>"#] {
+            assert!(parse_with_options(raw_xml, &options).is_ok());
+        }
+        for (raw_xml, name) in [
+            ("<1foo/>", "1foo"),
+            (r#"<a b%="c"/>"#, "b%"),
+            ("<-a></-a>", "-a"),
+        ] {
+            assert_eq!(
+                parse_with_options(raw_xml, &options).err(),
+                Some(ParseError::InvalidName(name.to_string()))
+            );
+        }
+        assert!(parse("<1foo/>").is_ok());
+    }
+
+    #[test]
+    fn a_visitor_counts_elements_by_name() {
+        #[derive(Default)]
+        struct NameCounter {
+            counts: HashMap<String, usize>,
+            texts: usize,
+        }
+        impl Visitor for NameCounter {
+            fn visit_element(&mut self, element: &XMLElement) {
+                *self
+                    .counts
+                    .entry(element.name().unwrap().to_string())
+                    .or_default() += 1;
+            }
+            fn visit_text(&mut self, _: &str) {
+                self.texts += 1;
+            }
+        }
+        let root = XMLElement::try_from("<a><b>x</b><b/><c><b>y</b></c><!--z--></a>").unwrap();
+        let mut counter = NameCounter::default();
+        root.accept(&mut counter);
+        assert_eq!(counter.counts["a"], 1);
+        assert_eq!(counter.counts["b"], 3);
+        assert_eq!(counter.counts["c"], 1);
+        assert_eq!(counter.texts, 2);
+    }
+
+    #[test]
+    fn text_right_after_an_end_tag_belongs_to_the_parent() {
+        let root = XMLElement::try_from("<a><b>x</b>y<c/>z</a>").unwrap();
+        let children = root.children();
+        assert_eq!(children[0].text(), Some("x"));
+        assert!(matches!(&children[1], XMLElement::Content(text) if text == "y"));
+        assert!(matches!(&children[3], XMLElement::Content(text) if text == "z"));
+        assert_eq!(children.len(), 4);
+    }
+
+    #[test]
+    fn attribute_names_can_be_normalized_to_lowercase() {
+        let options = ParseOptions {
+            normalize_attr_names: true,
+            ..Default::default()
+        };
+        let nodes = parse_with_options(r#"<a HREF="x" Class="y" id="z"/>"#, &options).unwrap();
+        assert_eq!(nodes[0].get_attr("href"), Some("x"));
+        assert_eq!(nodes[0].get_attr("class"), Some("y"));
+        assert_eq!(nodes[0].get_attr("HREF"), None);
+        assert_eq!(
+            parse_with_options(r#"<a HREF="x" href="y"/>"#, &options).err(),
+            Some(ParseError::DuplicateAttribute("href".to_string()))
+        );
+    }
+
+    #[test]
+    fn elements_are_found_by_attribute_value() {
+        let root = XMLElement::try_from(
+            r#"<page id="root"><div id="side"/><section><div id="main">m</div></section><p id="main"/></page>"#,
+        )
+        .unwrap();
+        let found = root.find_by_attr("id", "main");
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].text(), Some("m"));
+        assert_eq!(found[1].name(), Some("p"));
+        assert!(root.find_by_attr("id", "none").is_empty());
+    }
+
+    #[test]
+    fn top_level_text_between_elements_is_kept_in_order() {
+        let nodes = parse("<a>1</a>text<![CDATA[c]]><b/>tail").unwrap();
+        assert_eq!(nodes.len(), 5);
+        assert_eq!(nodes[0].name(), Some("a"));
+        assert!(matches!(&nodes[1], XMLElement::Content(text) if text == "text"));
+        assert!(matches!(&nodes[2], XMLElement::Cdata(text) if text == "c"));
+        assert_eq!(nodes[3].name(), Some("b"));
+        assert!(matches!(&nodes[4], XMLElement::Content(text) if text == "tail"));
+    }
+
+    #[test]
+    fn strings_are_escaped_for_text_and_attributes() {
+        let raw = r#"<a & 'b' "c">"#;
+        assert_eq!(escape_text(raw), r#"&lt;a &amp; 'b' "c"&gt;"#);
+        assert_eq!(
+            escape_attr(raw),
+            "&lt;a &amp; &apos;b&apos; &quot;c&quot;&gt;"
+        );
+        let written = format!(r#"<x v="{}" w='{}'/>"#, escape_attr(raw), escape_attr(raw));
+        let root = XMLElement::try_from(written.as_str()).unwrap();
+        assert_eq!(root.get_attr_decoded("v").as_deref(), Some(raw));
+        assert_eq!(root.get_attr_decoded("w").as_deref(), Some(raw));
+    }
+
+    #[test]
+    fn comments_may_follow_but_not_precede_the_declaration() {
+        let declaration = r#"<?xml version="1.0"?>"#;
+        let raw_xml = format!("{declaration}<!-- license --><a/>");
+        let nodes = parse(&raw_xml).unwrap();
+        assert!(matches!(&nodes[1], XMLElement::Comment(text) if text == " license "));
+        assert_eq!(nodes[2].name(), Some("a"));
+        let misplaced = format!("<!-- license -->{declaration}<a/>");
+        assert_eq!(
+            parse(&misplaced).err(),
+            Some(ParseError::MisplacedDeclaration)
+        );
+        assert!(Reader::new(&misplaced)
+            .any(|event| matches!(event, Err(ParseError::MisplacedDeclaration))));
+        assert!(parse("<!-- license --><a/>").is_ok());
+    }
+
+    #[test]
+    fn elements_are_renamed_at_every_level() {
+        let mut root =
+            XMLElement::try_from(r#"<oldtag a="1"><oldtag/><x><oldtag>t</oldtag></x></oldtag>"#)
+                .unwrap();
+        root.rename_all("oldtag", "newtag");
+        root.rename_attr_all("a", "b");
+        assert_eq!(
+            root.to_string(),
+            r#"<newtag b="1"><newtag/><x><newtag>t</newtag></x></newtag>"#
+        );
+    }
+
+    #[test]
+    fn invalid_utf8_reports_the_offset_of_the_bad_byte() {
+        assert_eq!(
+            parse_bytes(b"<a>\xff</a>").err(),
+            Some(ParseError::InvalidUtf8 { offset: 3 })
+        );
+        assert_eq!(
+            parse_bytes(b"\xEF\xBB\xBF<a>ok \xC3</a>").err(),
+            Some(ParseError::InvalidUtf8 { offset: 9 })
+        );
+    }
+
+    #[test]
+    fn greater_than_signs_never_end_a_section_early() {
+        let raw_xml = r#"<a x="1 > 0"><!-- <b> > --><![CDATA[ ]> > ]]><c y='>'/>t</a>"#;
+        let root = XMLElement::try_from(raw_xml).unwrap();
+        let children = root.children();
+        assert_eq!(root.get_attr("x"), Some("1 > 0"));
+        assert!(matches!(&children[0], XMLElement::Comment(text) if text == " <b> > "));
+        assert!(matches!(&children[1], XMLElement::Cdata(text) if text == " ]> > "));
+        assert_eq!(children[2].get_attr("y"), Some(">"));
+        assert_eq!(children.len(), 4);
+        assert_eq!(
+            root.to_string(),
+            r#"<a x="1 &gt; 0"><!-- <b> > --><![CDATA[ ]> > ]]><c y="&gt;"/>t</a>"#
+        );
+        assert_eq!(to_events(raw_xml).count(), 6);
+    }
+
+    #[test]
+    fn unknown_entities_are_errors_only_when_strict() {
+        let options = ParseOptions {
+            strict_entities: true,
+            decode_entities: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            parse_with_options("<a>&ampp;</a>", &options).err(),
+            Some(ParseError::UnknownEntity("ampp".to_string()))
+        );
+        let raw_xml = "<!DOCTYPE a [<!ENTITY x 'y'>]><a>&x;&amp;&#65;</a>";
+        let nodes = parse_with_options(raw_xml, &options).unwrap();
+        assert_eq!(nodes[1].inner_text(), "y&A");
+        let lenient = ParseOptions {
+            decode_entities: true,
+            ..Default::default()
+        };
+        let nodes = parse_with_options("<a>&ampp;</a>", &lenient).unwrap();
+        assert_eq!(nodes[0].inner_text(), "&ampp;");
+    }
+
+    #[test]
+    fn attribute_spans_slice_back_to_their_values() {
+        let raw_xml = "<a x=\"1\">\n  <b id=\"\u{e9}\" t='two words' flag/></a>";
+        let (_, span) = &parse_with_spans(raw_xml).unwrap()[0];
+        assert_eq!(
+            span.attribute_span("x").map(|range| &raw_xml[range]),
+            Some("1")
+        );
+        let b = &span.children[0];
+        assert_eq!(
+            b.attribute_span("id").map(|range| &raw_xml[range]),
+            Some("\u{e9}")
+        );
+        assert_eq!(
+            b.attribute_span("t").map(|range| &raw_xml[range]),
+            Some("two words")
+        );
+        assert_eq!(b.attribute_span("flag"), None);
+        assert_eq!(b.attribute_span("missing"), None);
+    }
+
+    #[test]
+    fn inline_comments_and_cdata_are_not_start_tags() {
+        let root = XMLElement::try_from("<a>x<!-- note --><b/><![CDATA[<raw/>]]></a>").unwrap();
+        let children = root.children();
+        assert!(matches!(&children[1], XMLElement::Comment(text) if text == " note "));
+        assert_eq!(children[2].name(), Some("b"));
+        assert!(matches!(&children[3], XMLElement::Cdata(text) if text == "<raw/>"));
+        assert_eq!(root.find_all("raw").len(), 0);
+    }
+
+    #[test]
+    fn linked_trees_are_walked_from_a_leaf_to_the_root() {
+        let root = XMLElement::try_from(r#"<a lang="en"><b><c>leaf</c></b></a>"#)
+            .unwrap()
+            .to_owned()
+            .into_linked_tree();
+        let leaf = root.children()[0].children()[0].children()[0].clone();
+        assert_eq!(leaf.element().text(), Some("leaf"));
+        let mut names = Vec::new();
+        let mut node = leaf.parent();
+        while let Some(parent) = node {
+            names.push(parent.element().name().unwrap().to_string());
+            node = parent.parent();
+        }
+        assert_eq!(names, ["c", "b", "a"]);
+        assert_eq!(
+            leaf.parent()
+                .unwrap()
+                .parent()
+                .unwrap()
+                .parent()
+                .unwrap()
+                .element()
+                .get_attr("lang"),
+            Some("en")
+        );
+        assert!(root.parent().is_none());
+    }
+
+    #[test]
+    fn unterminated_sections_become_error_events() {
+        let events: Vec<_> = to_events("<a><!--ok--><![CDATA[c]]></a>").collect();
+        assert!(matches!(&events[1], Event::Comment(text) if text == "ok"));
+        assert!(matches!(&events[2], Event::Cdata(text) if text == "c"));
+        assert!(!events.iter().any(|event| matches!(event, Event::Error(_))));
+        let events: Vec<_> = to_events("<a><!--ok--><!-- bad x</a>").collect();
+        assert!(matches!(&events[1], Event::Comment(text) if text == "ok"));
+        assert!(matches!(
+            events.last(),
+            Some(Event::Error(ParseError::UnterminatedTag))
+        ));
+    }
+
+    #[test]
+    fn child_text_map_of_a_flat_config() {
+        let config = XMLElement::try_from(
+            "<config><host>x</host><port>8080</port><!--c--><port>9090</port><empty/></config>",
+        )
+        .unwrap();
+        let map = config.child_text_map();
+        assert_eq!(map.len(), 3);
+        assert_eq!(map["host"], "x");
+        assert_eq!(map["port"], "9090");
+        assert_eq!(map["empty"], "");
+    }
+
+    #[test]
+    fn processing_instruction_targets_in_another_case_are_reserved() {
+        for (raw_xml, target) in [
+            ("<?XML-stylesheet href='a'?><a/>", "XML-stylesheet"),
+            ("<?Xml?><a/>", "Xml"),
+        ] {
+            assert_eq!(
+                parse(raw_xml).err(),
+                Some(ParseError::ReservedPiTarget(target.to_string()))
+            );
+        }
+        assert_eq!(parse("<?xml-stylesheet href='a'?><a/>").unwrap().len(), 2);
+    }
+
+    #[cfg(all(feature = "serde", feature = "serde_json"))]
+    #[test]
+    fn trees_round_trip_through_json() {
+        let raw_xml = r#"<a x="1" y>t<!--c--><![CDATA[d]]><?pi data?><b z="&amp;"/></a>"#;
+        let root = XMLElement::try_from(raw_xml).unwrap();
+        let json = serde_json::to_string(&root).unwrap();
+        let parsed: OwnedXMLElement = serde_json::from_str(&json).unwrap();
+        assert_eq!(parsed.to_string(), raw_xml);
+        assert_eq!(serde_json::to_string(&parsed).unwrap(), json);
+        assert!(serde_json::from_str::<OwnedXMLElement>(r#"{"type":"nope"}"#).is_err());
+    }
+
+    #[test]
+    fn the_writer_streams_a_small_document() {
+        let mut writer = Writer::new(Vec::new());
+        writer.write_start("a").unwrap();
+        writer.write_attr("x", "1 & \"2\"").unwrap();
+        writer.write_text("<t>").unwrap();
+        writer.write_empty("b").unwrap();
+        writer.write_attr("y", "z").unwrap();
+        writer.write_start("c").unwrap();
+        writer.write_end("c").unwrap();
+        writer.write_end("a").unwrap();
+        let bytes = writer.finish().unwrap();
+        assert_eq!(
+            bytes,
+            br#"<a x="1 &amp; &quot;2&quot;">&lt;t&gt;<b y="z"/><c/></a>"#
+        );
+        let mut writer = Writer::new(Vec::new());
+        writer.write_start("a").unwrap();
+        assert!(writer.write_end("b").is_err());
+        writer.write_text("t").unwrap();
+        assert!(writer.write_attr("x", "1").is_err());
+        assert!(writer.finish().is_err());
+    }
+
+    #[test]
+    fn attribute_values_may_span_lines() {
+        let raw_xml = "<a t=\"line1\nline2\r\n\tx\" u='y'/>";
+        let nodes = parse(raw_xml).unwrap();
+        assert_eq!(nodes[0].get_attr("t"), Some("line1\nline2\r\n\tx"));
+        assert_eq!(nodes[0].get_attr("u"), Some("y"));
+        let options = ParseOptions {
+            normalize_attr_whitespace: true,
+            ..Default::default()
+        };
+        let nodes = parse_with_options(raw_xml, &options).unwrap();
+        assert_eq!(nodes[0].get_attr("t"), Some("line1 line2  x"));
+    }
+
+    #[test]
+    fn trees_flatten_into_paths_and_texts() {
+        let root = XMLElement::try_from(
+            "<catalog><book><title>The Hobbit</title></book><book><title>Dune</title></book><note>n</note></catalog>",
+        )
+        .unwrap();
+        let pairs = root.flatten();
+        let pairs: Vec<(&str, &str)> = pairs
+            .iter()
+            .map(|(path, text)| (path.as_str(), text.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [
+                ("catalog/book[1]/title", "The Hobbit"),
+                ("catalog/book[2]/title", "Dune"),
+                ("catalog/note", "n"),
+            ]
+        );
+    }
+
+    #[test]
+    fn xml_space_preserve_keeps_whitespace_until_reset() {
+        let raw_xml = r#"<a> <b xml:space="preserve"> <c> </c><d xml:space="default"> <e xml:space="preserve"> </e></d></b> </a>"#;
+        let root = XMLElement::try_from(raw_xml).unwrap();
+        assert_eq!(
+            root.to_string(),
+            r#"<a><b xml:space="preserve"> <c> </c><d xml:space="default"><e xml:space="preserve"> </e></d></b></a>"#
+        );
+    }
+
+    #[test]
+    fn the_reader_honors_xml_space() {
+        let raw_xml =
+            r#"<a> <b xml:space="preserve"> <c> </c><d xml:space="default"> </d></b> </a>"#;
+        let texts = |options: &ParseOptions| {
+            Reader::with_options(raw_xml, options)
+                .filter(|event| matches!(event, Ok(Event::Text(_))))
+                .count()
+        };
+        assert_eq!(texts(&ParseOptions::default()), 2); // in b and c
+        let options = ParseOptions {
+            preserve_whitespace: true,
+            ..Default::default()
+        };
+        assert_eq!(texts(&options), 5);
+        assert!(matches!(
+            Reader::new(r#"<a xml:space="preserve"> </a>"#).nth(1),
+            Some(Ok(Event::Text(text))) if text == " "
+        ));
+    }
+
+    #[test]
+    fn tag_names_can_be_normalized_to_lowercase() {
+        let options = ParseOptions {
+            normalize_tag_names: true,
+            case_insensitive_tags: true,
+            ..Default::default()
+        };
+        let nodes =
+            parse_with_options("<DIV Class='x'><sPan>t</SPAN><BR/></DIV>", &options).unwrap();
+        assert_eq!(nodes[0].name(), Some("div"));
+        assert_eq!(nodes[0].children()[0].name(), Some("span"));
+        assert_eq!(
+            nodes[0].to_string(),
+            r#"<div Class="x"><span>t</span><br/></div>"#
+        );
+        assert_eq!(parse("<DIV/>").unwrap()[0].name(), Some("DIV"));
+    }
+
+    #[test]
+    fn children_are_inserted_and_removed() {
+        let mut root = XMLElement::try_from("<a><b/></a>").unwrap().to_owned();
+        root.push_child(XMLElement::EmptyElement(
+            Cow::Borrowed("d"),
+            Attributes::new(),
+        ))
+        .unwrap();
+        root.insert_child(1, XMLElement::Content(Cow::Borrowed("c")))
+            .unwrap();
+        assert_eq!(root.to_string(), "<a><b/>c<d/></a>");
+        let rejected = XMLElement::Content(Cow::Borrowed("x"));
+        assert!(root.insert_child(4, rejected).is_err());
+        assert_eq!(root.remove_child(0).unwrap().name(), Some("b"));
+        assert!(root.remove_child(2).is_none());
+        assert_eq!(root.to_string(), "<a>c<d/></a>");
+        let mut empty = XMLElement::EmptyElement(Cow::Borrowed("e"), Attributes::new());
+        empty
+            .push_child(XMLElement::Content(Cow::Borrowed("t")))
+            .unwrap();
+        assert_eq!(empty.to_string(), "<e>t</e>");
+        assert!(XMLElement::Content(Cow::Borrowed("t"))
+            .push_child(empty)
+            .is_err());
+    }
 }